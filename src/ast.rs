@@ -1,73 +1,42 @@
 use super::{Annot, Loc};
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum OpKind {
-  Add,
-  Sub,
-  Mul,
-  Div,
-  Lt,
-  Equal,
-  Gt,
-  And,
-  Or,
-}
-
-pub type Op = Annot<OpKind>;
-
-impl Op {
-  pub fn add(loc: Loc) -> Self {
-    Self::new(OpKind::Add, loc)
-  }
-  pub fn sub(loc: Loc) -> Self {
-    Self::new(OpKind::Sub, loc)
-  }
-  pub fn mul(loc: Loc) -> Self {
-    Self::new(OpKind::Mul, loc)
-  }
-  pub fn div(loc: Loc) -> Self {
-    Self::new(OpKind::Div, loc)
-  }
-  pub fn lt(loc: Loc) -> Self {
-    Self::new(OpKind::Lt, loc)
-  }
-  pub fn equal(loc: Loc) -> Self {
-    Self::new(OpKind::Equal, loc)
-  }
-  pub fn gt(loc: Loc) -> Self {
-    Self::new(OpKind::Gt, loc)
-  }
-  pub fn and(loc: Loc) -> Self {
-    Self::new(OpKind::And, loc)
-  }
-  pub fn or(loc: Loc) -> Self {
-    Self::new(OpKind::Or, loc)
-  }
-}
+use super::number::Number;
+use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AstKind {
-  Num(u32),
-  Op    { op: Op },
+  Num(Number),
+  Boolean(bool),
+  Symbol(Box<str>),
+  String(Rc<str>),
+  Char(char),
   Nil,
-  Pair  { l: Box<Ast>, r: Box<Ast> },
-  Quote { q: Box<Ast> },
+  Pair  { l: Rc<Ast>, r: Rc<Ast> },
+  Quote { q: Rc<Ast> },
+  /// Stands in for input that failed to parse.
+  Error,
 }
 
 pub type Ast = Annot<AstKind>;
 
 impl Ast {
-  pub fn num(n: u32, loc: Loc) -> Self {
+  pub fn num(n: Number, loc: Loc) -> Self {
     Self::new(AstKind::Num(n), loc)
   }
 
-  pub fn op(op: Op, loc: Loc) -> Self {
-    Self::new(
-      AstKind::Op {
-        op: op
-      },
-      loc,
-    )
+  pub fn boolean(b: bool, loc: Loc) -> Self {
+    Self::new(AstKind::Boolean(b), loc)
+  }
+
+  pub fn symbol(name: &str, loc: Loc) -> Self {
+    Self::new(AstKind::Symbol(Box::from(name)), loc)
+  }
+
+  pub fn string(s: &str, loc: Loc) -> Self {
+    Self::new(AstKind::String(Rc::from(s)), loc)
+  }
+
+  pub fn character(c: char, loc: Loc) -> Self {
+    Self::new(AstKind::Char(c), loc)
   }
 
   pub fn nil(loc: Loc) -> Self {
@@ -80,19 +49,52 @@ impl Ast {
   pub fn pair(l: Ast, r: Ast, loc: Loc) -> Self {
     Self::new(
       AstKind::Pair {
-        l: Box::new(l),
-        r: Box::new(r),
+        l: Rc::new(l),
+        r: Rc::new(r),
       },
       loc,
     )
   }
 
+  pub fn error(loc: Loc) -> Self {
+    Self::new(AstKind::Error, loc)
+  }
+
   pub fn quote(q: Ast, loc: Loc) -> Self {
     Self::new(
       AstKind::Quote {
-        q: Box::new(q),
+        q: Rc::new(q),
       },
       loc,
     )
   }
-}
\ No newline at end of file
+}
+impl Drop for AstKind {
+  /// Frees the spine of a list one pair at a time, since dropping the nested
+  /// `Rc`s recursively would overflow the stack on long lists.
+  fn drop(&mut self) {
+    let mut next = match self.take_tail() {
+      Some(next) => next,
+      None => return,
+    };
+    while let Ok(mut pair) = Rc::try_unwrap(next) {
+      next = match pair.value.take_tail() {
+        Some(next) => next,
+        None => return,
+      };
+    }
+  }
+}
+
+impl AstKind {
+  /// Detaches the cdr of a pair, leaving nil in its place.
+  fn take_tail(&mut self) -> Option<Rc<Ast>> {
+    match self {
+      AstKind::Pair { r, .. } if Rc::strong_count(r) == 1 => {
+        let loc = r.loc;
+        Some(std::mem::replace(r, Rc::new(Ast::nil(loc))))
+      },
+      _ => None,
+    }
+  }
+}
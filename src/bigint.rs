@@ -0,0 +1,492 @@
+use std::cmp::Ordering;
+
+/// An arbitrary-precision integer, stored as a sign and a magnitude in base
+/// 2^32 limbs, least significant first. The magnitude has no high zero limbs,
+/// so zero is the empty magnitude and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+  negative: bool,
+  mag: Vec<u32>,
+}
+
+impl BigInt {
+  fn new(negative: bool, mut mag: Vec<u32>) -> Self {
+    while mag.last() == Some(&0) {
+      mag.pop();
+    }
+    let negative = negative && !mag.is_empty();
+    BigInt { negative, mag }
+  }
+  pub fn zero() -> Self {
+    BigInt::new(false, Vec::new())
+  }
+  pub fn from_i64(n: i64) -> Self {
+    let m = n.unsigned_abs();
+    BigInt::new(n < 0, vec![m as u32, (m >> 32) as u32])
+  }
+  /// Parses unsigned `digits` in `radix`; the caller has checked they are valid.
+  pub fn from_digits(negative: bool, digits: impl Iterator<Item = u32>, radix: u32) -> Self {
+    let mut mag = Vec::new();
+    for digit in digits {
+      mul_add_small(&mut mag, radix, digit);
+    }
+    BigInt::new(negative, mag)
+  }
+  /// The integer value of a finite, integral float.
+  pub fn from_f64(x: f64) -> Self {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let mantissa = if exponent == 0 { fraction << 1 } else { fraction | (1 << 52) };
+    let shift = exponent - 1075;
+    let mag = vec![mantissa as u32, (mantissa >> 32) as u32];
+    let mag = if shift >= 0 { shl(&mag, shift as usize) } else { shr(&mag, (-shift) as usize) };
+    BigInt::new(x < 0.0, mag)
+  }
+  pub fn is_zero(&self) -> bool {
+    self.mag.is_empty()
+  }
+  pub fn is_negative(&self) -> bool {
+    self.negative
+  }
+  /// The value as an `i32`, if it fits.
+  pub fn to_i32(&self) -> Option<i32> {
+    let m = match self.mag[..] {
+      [] => 0,
+      [m] => i64::from(m),
+      _ => return None,
+    };
+    let n = if self.negative { -m } else { m };
+    if n < i64::from(i32::MIN) || n > i64::from(i32::MAX) {
+      return None;
+    }
+    Some(n as i32)
+  }
+  pub fn to_f64(&self) -> f64 {
+    let m = self.mag.iter().rev().fold(0.0, |acc, &limb| acc * 4294967296.0 + f64::from(limb));
+    if self.negative { -m } else { m }
+  }
+  pub fn neg(&self) -> Self {
+    BigInt::new(!self.negative, self.mag.clone())
+  }
+  pub fn abs(&self) -> Self {
+    BigInt::new(false, self.mag.clone())
+  }
+  pub fn add(&self, other: &BigInt) -> Self {
+    if self.negative == other.negative {
+      return BigInt::new(self.negative, add(&self.mag, &other.mag));
+    }
+    match cmp(&self.mag, &other.mag) {
+      Ordering::Less => BigInt::new(other.negative, sub(&other.mag, &self.mag)),
+      _ => BigInt::new(self.negative, sub(&self.mag, &other.mag)),
+    }
+  }
+  pub fn sub(&self, other: &BigInt) -> Self {
+    self.add(&other.neg())
+  }
+  pub fn mul(&self, other: &BigInt) -> Self {
+    BigInt::new(self.negative != other.negative, mul(&self.mag, &other.mag))
+  }
+  /// Truncating division: the quotient rounds toward zero and the remainder
+  /// takes the sign of the dividend. The divisor must not be zero.
+  pub fn div_rem(&self, other: &BigInt) -> (Self, Self) {
+    let (q, r) = div_rem(&self.mag, &other.mag);
+    (BigInt::new(self.negative != other.negative, q), BigInt::new(self.negative, r))
+  }
+  pub fn pow(&self, mut exponent: u32) -> Self {
+    let mut base = self.clone();
+    let mut acc = BigInt::from_i64(1);
+    while exponent > 0 {
+      if exponent & 1 == 1 {
+        acc = acc.mul(&base);
+      }
+      exponent >>= 1;
+      if exponent > 0 {
+        base = base.mul(&base);
+      }
+    }
+    acc
+  }
+  /// The greatest common divisor, never negative.
+  pub fn gcd(&self, other: &BigInt) -> Self {
+    let (mut a, mut b) = (self.abs(), other.abs());
+    while !b.is_zero() {
+      let r = a.div_rem(&b).1;
+      a = b;
+      b = r;
+    }
+    a
+  }
+  /// Bitwise and, as if both were in infinite two's complement.
+  pub fn and(&self, other: &BigInt) -> Self {
+    self.bitwise(other, |a, b| a & b)
+  }
+  /// Bitwise inclusive or, as if both were in infinite two's complement.
+  pub fn or(&self, other: &BigInt) -> Self {
+    self.bitwise(other, |a, b| a | b)
+  }
+  fn bitwise(&self, other: &BigInt, f: fn(u32, u32) -> u32) -> Self {
+    // one limb more than either magnitude holds the sign
+    let len = self.mag.len().max(other.mag.len()) + 1;
+    let (a, b) = (self.to_twos_complement(len), other.to_twos_complement(len));
+    let mut limbs = a.iter().zip(&b).map(|(&a, &b)| f(a, b)).collect::<Vec<_>>();
+    let negative = limbs[len - 1] >> 31 == 1;
+    if negative {
+      negate(&mut limbs);
+    }
+    BigInt::new(negative, limbs)
+  }
+  /// The value in two's complement, in `len` limbs.
+  fn to_twos_complement(&self, len: usize) -> Vec<u32> {
+    let mut limbs = self.mag.clone();
+    limbs.resize(len, 0);
+    if self.negative {
+      negate(&mut limbs);
+    }
+    limbs
+  }
+  /// The number of bits in the magnitude, 0 for zero.
+  pub fn bits(&self) -> usize {
+    self.mag.len() * 32 - self.mag.last().map_or(0, |m| m.leading_zeros() as usize)
+  }
+  /// The largest integer whose square does not exceed this non-negative number.
+  pub fn isqrt(&self) -> Self {
+    if self.is_zero() {
+      return self.clone();
+    }
+    // start above the root and descend with Newton's method
+    let mut x = BigInt::new(false, shl(&[1], self.bits().div_ceil(2)));
+    loop {
+      let y = x.add(&self.div_rem(&x).0);
+      let y = BigInt::new(false, shr(&y.mag, 1));
+      if y.cmp(&x) != Ordering::Less {
+        return x;
+      }
+      x = y;
+    }
+  }
+  /// Decimal digits, with a leading `-` if negative.
+  pub fn to_decimal(&self) -> String {
+    self.to_radix(10)
+  }
+  /// Lowercase digits in `radix`, from 2 to 36, with a leading `-` if negative.
+  pub fn to_radix(&self, radix: u32) -> String {
+    // peel off as many digits at a time as a limb can hold
+    let (mut chunk, mut width) = (radix, 1);
+    while let Some(next) = chunk.checked_mul(radix) {
+      chunk = next;
+      width += 1;
+    }
+    let mut mag = self.mag.clone();
+    let mut chunks = Vec::new();
+    while !mag.is_empty() {
+      let r = div_rem_small(&mut mag, chunk);
+      chunks.push(r);
+    }
+    let mut s = String::from(if self.negative { "-" } else { "" });
+    match chunks.split_last() {
+      Some((first, rest)) => {
+        push_digits(&mut s, *first, radix, 0);
+        for &chunk in rest.iter().rev() {
+          push_digits(&mut s, chunk, radix, width);
+        }
+      },
+      None => s.push('0'),
+    }
+    s
+  }
+}
+
+impl PartialOrd for BigInt {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for BigInt {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self.negative, other.negative) {
+      (false, true) => Ordering::Greater,
+      (true, false) => Ordering::Less,
+      (false, false) => cmp(&self.mag, &other.mag),
+      (true, true) => cmp(&other.mag, &self.mag),
+    }
+  }
+}
+
+/// Appends `n` in `radix`, padded with zeros to `width` digits.
+fn push_digits(s: &mut String, mut n: u32, radix: u32, width: usize) {
+  let mut digits = Vec::new();
+  while n > 0 || digits.len() < width.max(1) {
+    digits.push(std::char::from_digit(n % radix, radix).unwrap_or('?'));
+    n /= radix;
+  }
+  s.extend(digits.iter().rev());
+}
+
+/// Negates a two's complement number in place.
+fn negate(limbs: &mut [u32]) {
+  let mut carry = true;
+  for limb in limbs {
+    let (n, overflow) = (!*limb).overflowing_add(u32::from(carry));
+    *limb = n;
+    carry = overflow;
+  }
+}
+
+fn cmp(a: &[u32], b: &[u32]) -> Ordering {
+  a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let (a, b) = if a.len() < b.len() { (b, a) } else { (a, b) };
+  let mut sum = Vec::with_capacity(a.len() + 1);
+  let mut carry = 0;
+  for (i, &x) in a.iter().enumerate() {
+    let s = u64::from(x) + u64::from(b.get(i).copied().unwrap_or(0)) + carry;
+    sum.push(s as u32);
+    carry = s >> 32;
+  }
+  sum.push(carry as u32);
+  sum
+}
+
+/// `a - b` where `a >= b`.
+fn sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let mut diff = Vec::with_capacity(a.len());
+  let mut borrow = 0;
+  for (i, &x) in a.iter().enumerate() {
+    let d = i64::from(x) - i64::from(b.get(i).copied().unwrap_or(0)) - borrow;
+    diff.push(d as u32);
+    borrow = if d < 0 { 1 } else { 0 };
+  }
+  diff
+}
+
+fn mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let mut product = vec![0u32; a.len() + b.len()];
+  for (i, &x) in a.iter().enumerate() {
+    let mut carry = 0;
+    for (j, &y) in b.iter().enumerate() {
+      let p = u64::from(x) * u64::from(y) + u64::from(product[i + j]) + carry;
+      product[i + j] = p as u32;
+      carry = p >> 32;
+    }
+    product[i + b.len()] = carry as u32;
+  }
+  product
+}
+
+fn mul_add_small(mag: &mut Vec<u32>, m: u32, a: u32) {
+  let mut carry = u64::from(a);
+  for limb in mag.iter_mut() {
+    let p = u64::from(*limb) * u64::from(m) + carry;
+    *limb = p as u32;
+    carry = p >> 32;
+  }
+  if carry > 0 {
+    mag.push(carry as u32);
+  }
+}
+
+/// Divides `mag` in place by `d`, dropping high zero limbs, and returns the remainder.
+fn div_rem_small(mag: &mut Vec<u32>, d: u32) -> u32 {
+  let mut r = 0u64;
+  for limb in mag.iter_mut().rev() {
+    let n = (r << 32) | u64::from(*limb);
+    *limb = (n / u64::from(d)) as u32;
+    r = n % u64::from(d);
+  }
+  while mag.last() == Some(&0) {
+    mag.pop();
+  }
+  r as u32
+}
+
+fn shl(a: &[u32], bits: usize) -> Vec<u32> {
+  let (limbs, bits) = (bits / 32, bits % 32);
+  let mut out = vec![0u32; limbs];
+  let mut carry = 0;
+  for &x in a {
+    out.push((x << bits) | carry);
+    carry = if bits == 0 { 0 } else { x >> (32 - bits) };
+  }
+  out.push(carry);
+  out
+}
+
+fn shr(a: &[u32], bits: usize) -> Vec<u32> {
+  let (limbs, bits) = (bits / 32, bits % 32);
+  let a = a.get(limbs..).unwrap_or(&[]);
+  (0..a.len())
+    .map(|i| {
+      let high = if bits == 0 { 0 } else { a.get(i + 1).map_or(0, |&x| x << (32 - bits)) };
+      (a[i] >> bits) | high
+    })
+    .collect()
+}
+
+/// Long division of magnitudes (Knuth, TAOCP vol. 2, algorithm D).
+fn div_rem(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+  if cmp(u, v) == Ordering::Less {
+    return (Vec::new(), u.to_vec());
+  }
+  if let [d] = v[..] {
+    let mut q = u.to_vec();
+    let r = div_rem_small(&mut q, d);
+    return (q, vec![r]);
+  }
+  // normalize so that the top limb of the divisor has its high bit set
+  let s = v[v.len() - 1].leading_zeros() as usize;
+  let v = &shl(v, s)[..v.len()];
+  let mut u = shl(u, s);
+  let n = v.len();
+  let m = u.len() - n - 1;
+  let mut q = vec![0u32; m + 1];
+  for j in (0..=m).rev() {
+    let num = (u64::from(u[j + n]) << 32) | u64::from(u[j + n - 1]);
+    let mut qhat = num / u64::from(v[n - 1]);
+    let mut rhat = num % u64::from(v[n - 1]);
+    while qhat >= 1 << 32 || qhat * u64::from(v[n - 2]) > ((rhat << 32) | u64::from(u[j + n - 2])) {
+      qhat -= 1;
+      rhat += u64::from(v[n - 1]);
+      if rhat >= 1 << 32 {
+        break;
+      }
+    }
+    // subtract qhat * v from the current window of u
+    let mut borrow = 0i64;
+    let mut carry = 0u64;
+    for i in 0..n {
+      let p = qhat * u64::from(v[i]) + carry;
+      carry = p >> 32;
+      let t = i64::from(u[i + j]) - borrow - (p & 0xffff_ffff) as i64;
+      u[i + j] = t as u32;
+      borrow = if t < 0 { 1 } else { 0 };
+    }
+    let t = i64::from(u[j + n]) - borrow - carry as i64;
+    u[j + n] = t as u32;
+    // qhat was one too large: add v back
+    if t < 0 {
+      qhat -= 1;
+      let mut carry = 0u64;
+      for i in 0..n {
+        let s = u64::from(u[i + j]) + u64::from(v[i]) + carry;
+        u[i + j] = s as u32;
+        carry = s >> 32;
+      }
+      u[j + n] = u[j + n].wrapping_add(carry as u32);
+    }
+    q[j] = qhat as u32;
+  }
+  (q, shr(&u[..n], s))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn big(s: &str) -> BigInt {
+    let (negative, digits) = match s.strip_prefix('-') {
+      Some(digits) => (true, digits),
+      None => (false, s),
+    };
+    BigInt::from_digits(negative, digits.chars().map(|c| c.to_digit(10).unwrap()), 10)
+  }
+
+  fn div_rem(u: &str, v: &str) -> (String, String) {
+    let (q, r) = big(u).div_rem(&big(v));
+    (q.to_decimal(), r.to_decimal())
+  }
+
+  #[test]
+  fn div_rem_cases() {
+    let s = |q: &str, r: &str| (String::from(q), String::from(r));
+    // 2^96 / (2^64 + 1) overestimates a quotient digit and adds the divisor back
+    assert_eq!(div_rem("79228162514264337593543950336", "18446744073709551617"), s("4294967295", "18446744069414584321"));
+    // the divisor's top limb is small, so both operands are shifted first
+    assert_eq!(div_rem("-1000000000000000000000000000007", "1099511627779"), s("-909494701770446696", "-85087631823"));
+    // single-limb divisors
+    assert_eq!(div_rem("1000000000000000000000000000007", "7"), s("142857142857142857142857142858", "1"));
+    assert_eq!(div_rem("1000000000000000000000000000007", "4294967295"), s("232830643708079737543", "631343822"));
+    assert_eq!(div_rem("5", "-1000000000000000000000"), s("0", "5"));
+    assert_eq!(div_rem("-7", "2"), s("-3", "-1"));
+    // quotients that fit an i32 again, down to i32::MIN
+    let (q, _) = big("-4611686018427387904").div_rem(&big("2147483648"));
+    assert_eq!(q.to_i32(), Some(i32::MIN));
+    assert_eq!(big("2147483648").to_i32(), None);
+  }
+
+  #[test]
+  fn bitwise_uses_twos_complement() {
+    let and = |a: &str, b: &str| big(a).and(&big(b)).to_decimal();
+    let or = |a: &str, b: &str| big(a).or(&big(b)).to_decimal();
+    assert_eq!(and("1099511627776", "1"), "0");
+    assert_eq!(and("1099511627775", "-256"), "1099511627520");
+    assert_eq!(and("-5", "1180591620717411303423"), "1180591620717411303419");
+    assert_eq!(and("-18446744073709551616", "-9223372036854775808"), "-18446744073709551616");
+    assert_eq!(or("-1099511627776", "255"), "-1099511627521");
+    assert_eq!(or("-5", "1180591620717411303424"), "-5");
+    assert_eq!(or("18446744073709551616", "4294967296"), "18446744078004518912");
+    assert_eq!(or("0", "-1"), "-1");
+  }
+
+  #[test]
+  fn div_rem_recombines() {
+    // a small linear congruential generator, for operands of varied lengths
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut limbs = |n: usize| {
+      (0..n).map(|_| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        // mix in extreme limbs, which exercise the correction steps
+        match seed >> 61 {
+          0 => 0,
+          1 => u32::MAX,
+          2 => 1 << 31,
+          _ => (seed >> 32) as u32,
+        }
+      }).collect::<Vec<_>>()
+    };
+    for i in 0..2000 {
+      let u = BigInt::new(i % 2 == 0, limbs(1 + i % 7));
+      let v = BigInt::new(i % 3 == 0, limbs(1 + i % 4));
+      if v.is_zero() {
+        continue;
+      }
+      let (q, r) = u.div_rem(&v);
+      assert_eq!(q.mul(&v).add(&r), u);
+      assert_eq!(cmp(&r.mag, &v.mag), Ordering::Less);
+      assert!(r.is_zero() || r.is_negative() == u.is_negative());
+    }
+  }
+
+  #[test]
+  fn gcd() {
+    assert_eq!(big("-12").gcd(&big("18")), big("6"));
+    assert_eq!(big("0").gcd(&big("-5")), big("5"));
+    assert_eq!(big("0").gcd(&big("0")), big("0"));
+    // 2^64 * 3^20 and 6^30
+    assert_eq!(big("64319819485449658779373142016").gcd(&big("221073919720733357899776")), big("3743906242624487424"));
+  }
+
+  #[test]
+  fn isqrt() {
+    for n in 0..1000u32 {
+      let root = BigInt::from_i64(i64::from(n)).isqrt().to_i32().unwrap();
+      assert!(root * root <= n as i32 && (root + 1) * (root + 1) > n as i32, "{}", n);
+    }
+    assert_eq!(big("9999999999999999999999999999999999999999").isqrt(), big("99999999999999999999"));
+    assert_eq!(big("170141183460469231731687303715884105728").isqrt(), big("13043817825332782212"));
+  }
+
+  #[test]
+  fn to_radix() {
+    assert_eq!(BigInt::zero().to_radix(2), "0");
+    assert_eq!(big("-255").to_radix(16), "-ff");
+    assert_eq!(big("1267650600228229401496703205375").to_radix(16), "fffffffffffffffffffffffff");
+    assert_eq!(big("-37778931862957161709568").to_radix(8), "-10000000000000000000000000");
+    // a chunk boundary must keep its zeros
+    assert_eq!(big("1000000000000000000000000000007").to_decimal(), "1000000000000000000000000000007");
+    assert_eq!(big("35").to_radix(36), "z");
+  }
+}
@@ -1,13 +1,175 @@
 use super::{Loc, Annot};
+use super::ast::Ast;
+use super::env::{Env, EnvRef};
 use super::interpreter::InterpreterError;
+use super::number::Number;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
 use super::interpreter::InterpreterErrorKind::*;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A builtin receives its evaluated arguments and the location of the call.
+pub type BuiltinFn = fn(Vec<Data>, Loc) -> Result<Data, InterpreterError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DataKind {
-  Num(i32),
+  Num(Number),
   Boolean(bool),
   Nil,
+  /// The value of forms such as `(display x)` that have no useful result.
+  Unspecified,
   Symbol(Box<str>),
+  String(Rc<str>),
+  Char(char),
+  Pair  { car: Rc<Data>, cdr: Rc<Data> },
+  Builtin(Builtin),
+  Closure(Rc<Closure>),
+}
+
+impl Drop for DataKind {
+  /// Frees nested pairs with an explicit stack, since dropping the nested
+  /// `Rc`s recursively would overflow the stack on long or deep lists.
+  fn drop(&mut self) {
+    let mut pending = Vec::new();
+    self.take_children(&mut pending);
+    while let Some(next) = pending.pop() {
+      if let Ok(mut data) = Rc::try_unwrap(next) {
+        data.value.take_children(&mut pending);
+      }
+    }
+  }
+}
+
+impl DataKind {
+  /// Detaches the car and cdr of a pair no other value shares, leaving nil
+  /// in their place.
+  fn take_children(&mut self, pending: &mut Vec<Rc<Data>>) {
+    if let Pair { car, cdr } = self {
+      for child in [car, cdr] {
+        if Rc::strong_count(child) == 1 {
+          let loc = child.loc;
+          pending.push(std::mem::replace(child, Rc::new(Data::nil(loc))));
+        }
+      }
+    }
+  }
+}
+
+/// A procedure implemented in Rust, possibly capturing host state.
+#[derive(Clone)]
+pub struct Builtin {
+  pub name: &'static str,
+  pub f: Rc<dyn Fn(Vec<Data>, Loc) -> Result<Data, InterpreterError>>,
+}
+
+/// A user-defined procedure together with the environment it was defined in.
+pub struct Closure {
+  pub name: Option<Rc<str>>,
+  pub params: Rc<[Box<str>]>,
+  pub rest: Option<Rc<str>>,
+  pub body: Rc<[Ast]>,
+  env: Captured,
+  /// Where the `lambda` or `define` form creating it appeared.
+  pub loc: Loc,
+}
+
+/// How a closure holds its environment. A closure bound in the environment
+/// it captures holds it weakly, or the two would keep each other alive;
+/// looking the binding up gives back a closure holding it strongly.
+///
+/// A weak closure therefore never outlives its environment: `Env::define` is
+/// the only place one is made, it is stored in the very environment it
+/// points to, and `Env` upgrades a binding before handing it out. Reaching a
+/// weak closure means going through its environment, which is then alive.
+enum Captured {
+  Strong(EnvRef),
+  Weak(Weak<RefCell<Env>>),
+}
+
+impl PartialEq for Builtin {
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name
+  }
+}
+
+impl Eq for Builtin {}
+
+impl fmt::Debug for Builtin {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Builtin({})", self.name)
+  }
+}
+
+impl Closure {
+  pub fn new(name: Option<Rc<str>>, params: Vec<Box<str>>, rest: Option<Rc<str>>, body: Vec<Ast>, env: &EnvRef, loc: Loc) -> Self {
+    Closure { name, params: Rc::from(params), rest, body: Rc::from(body), env: Captured::Strong(env.clone()), loc }
+  }
+  /// The environment the closure was defined in.
+  pub fn env(&self) -> EnvRef {
+    match self.env {
+      Captured::Strong(ref env) => env.clone(),
+      // cannot fail, see `Captured`
+      Captured::Weak(ref env) => env.upgrade().expect("closure outlived its environment"),
+    }
+  }
+  pub fn is_weak(&self) -> bool {
+    matches!(self.env, Captured::Weak(_))
+  }
+  /// Whether this closure holds `env` strongly.
+  pub fn captures(&self, env: &Env) -> bool {
+    matches!(self.env, Captured::Strong(ref captured) if std::ptr::eq(captured.as_ptr(), env))
+  }
+  /// The same procedure, holding its environment weakly. Only for a
+  /// binding in that environment, as made by `Env::define`.
+  pub fn downgrade(&self) -> Closure {
+    let env = match self.env {
+      Captured::Strong(ref env) => Captured::Weak(Rc::downgrade(env)),
+      Captured::Weak(ref env) => Captured::Weak(env.clone()),
+    };
+    Closure { env, ..self.share() }
+  }
+  /// The same procedure, holding its environment strongly.
+  pub fn upgrade(&self) -> Closure {
+    Closure { env: Captured::Strong(self.env()), ..self.share() }
+  }
+  /// A copy sharing the code but not yet the environment.
+  fn share(&self) -> Closure {
+    Closure {
+      name: self.name.clone(),
+      params: self.params.clone(),
+      rest: self.rest.clone(),
+      body: self.body.clone(),
+      env: Captured::Weak(Weak::new()),
+      loc: self.loc,
+    }
+  }
+}
+
+/// Closures are the same procedure if they come from the same evaluation
+/// of a `lambda` and share an environment, however they hold it.
+impl PartialEq for Closure {
+  fn eq(&self, other: &Self) -> bool {
+    let env = |closure: &Closure| match closure.env {
+      Captured::Strong(ref env) => Rc::as_ptr(env),
+      Captured::Weak(ref env) => env.as_ptr(),
+    };
+    Rc::ptr_eq(&self.body, &other.body) && env(self) == env(other)
+  }
+}
+
+impl Eq for Closure {}
+
+// the captured environment usually refers back to the closure itself
+impl fmt::Debug for Closure {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Closure")
+      .field("name", &self.name)
+      .field("params", &self.params)
+      .field("rest", &self.rest)
+      .finish()
+  }
 }
 
 pub type Data = Annot<DataKind>;
@@ -15,6 +177,9 @@ use DataKind::*;
 
 impl Data {
   pub fn num(n: i32, loc: Loc) -> Self {
+    Self::number(Number::Int(n), loc)
+  }
+  pub fn number(n: Number, loc: Loc) -> Self {
     Data::new (
       Num(n),
       loc,
@@ -32,109 +197,639 @@ impl Data {
       loc,
     )
   }
+  pub fn unspecified(loc: Loc) -> Self {
+    Data::new (
+      Unspecified,
+      loc,
+    )
+  }
   pub fn symbol(name: &str, loc: Loc) -> Self {
     Data::new (
       Symbol(Box::from(name)),
       loc,
     )
   }
-  pub fn add(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::num(l + r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  pub fn string(s: impl Into<Rc<str>>, loc: Loc) -> Self {
+    Data::new (
+      String(s.into()),
+      loc,
+    )
+  }
+  pub fn character(c: char, loc: Loc) -> Self {
+    Data::new (
+      Char(c),
+      loc,
+    )
+  }
+  pub fn pair(car: Data, cdr: Data, loc: Loc) -> Self {
+    Data::new (
+      Pair {
+        car: Rc::new(car),
+        cdr: Rc::new(cdr),
+      },
+      loc,
+    )
+  }
+  pub fn builtin<F>(name: &'static str, f: F, loc: Loc) -> Self
+  where
+    F: Fn(Vec<Data>, Loc) -> Result<Data, InterpreterError> + 'static,
+  {
+    Data::new (
+      DataKind::Builtin(Builtin { name, f: Rc::new(f) }),
+      loc,
+    )
+  }
+  pub fn closure(closure: Closure, loc: Loc) -> Self {
+    Data::new (
+      DataKind::Closure(Rc::new(closure)),
+      loc,
+    )
+  }
+  /// Only `#f` counts as false; every other value, including `()` and `0`, is true.
+  pub fn is_truthy(&self) -> bool {
+    self.value != Boolean(false)
+  }
+  /// Whether this is a value the REPL does not print.
+  pub fn is_unspecified(&self) -> bool {
+    self.value == Unspecified
+  }
+  /// Builds a proper list terminated by `()`.
+  pub fn list(items: Vec<Data>, loc: Loc) -> Self {
+    items.into_iter().rev().fold(Self::nil(loc), |cdr, car| Self::pair(car, cdr, loc))
+  }
+  /// Converts a quoted expression into the value it denotes.
+  pub fn quote(ast: &Ast) -> Self {
+    use super::ast::AstKind as A;
+    match ast.value {
+      A::Num(ref n) => Self::number(n.clone(), ast.loc),
+      A::Boolean(b) => Self::boolean(b, ast.loc),
+      A::Nil => Self::nil(ast.loc),
+      A::Symbol(ref name) => Self::symbol(name, ast.loc),
+      A::String(ref s) => Self::string(s.clone(), ast.loc),
+      A::Char(c) => Self::character(c, ast.loc),
+      A::Pair { ref l, ref r } => Self::pair(Self::quote(l), Self::quote(r), ast.loc),
+      A::Quote { ref q } => Self::list(vec![Self::symbol("quote", ast.loc), Self::quote(q)], ast.loc),
+      // only programs that failed to parse contain error nodes
+      A::Error => Self::nil(ast.loc),
     }
   }
-  pub fn sub(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::num(l - r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  /// Extracts the numeric arguments of a builtin.
+  fn nums(args: &[Data]) -> Result<Vec<Number>, InterpreterError> {
+    args.iter()
+      .map(|arg| match arg.value {
+        Num(ref n) => Ok(n.clone()),
+        _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
+      })
+      .collect()
+  }
+  /// Extracts the string arguments of a builtin.
+  fn strs(args: &[Data]) -> Result<Vec<Rc<str>>, InterpreterError> {
+    args.iter()
+      .map(|arg| match arg.value {
+        String(ref s) => Ok(s.clone()),
+        _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
+      })
+      .collect()
+  }
+  /// The only argument of a builtin taking one character.
+  fn one_char(args: Vec<Data>, loc: Loc) -> Result<char, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    match arg.value {
+      Char(c) => Ok(c),
+      _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
     }
   }
-  pub fn mul(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::num(l * r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  /// Extracts a non-negative `i32` argument, such as an index.
+  fn index(arg: &Data) -> Result<usize, InterpreterError> {
+    match arg.value {
+      Num(Number::Int(n)) if n >= 0 => Ok(n as usize),
+      _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
     }
   }
-  pub fn div(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::num(l / r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
+  /// The elements of a proper list, or `None` if `list` is not one.
+  fn items(list: &Data) -> Option<Vec<Data>> {
+    let mut items = Vec::new();
+    let mut rest = list;
+    loop {
+      match rest.value {
+        Pair { ref car, ref cdr } => {
+          items.push((**car).clone());
+          rest = cdr;
+        },
+        Nil => return Some(items),
+        _ => return None,
       }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
     }
   }
-  pub fn gt(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::boolean(l > r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  /// Extracts integer arguments as exact numbers, and whether any was inexact.
+  fn integers(args: &[Data]) -> Result<(Vec<Number>, bool), InterpreterError> {
+    let ns = args.iter()
+      .map(|arg| match arg.value {
+        Num(ref n) => n.to_exact()
+          .filter(Number::is_integer)
+          .ok_or_else(|| InterpreterError::new(InvalidArguments, arg.loc)),
+        _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let inexact = args.iter().any(|arg| matches!(arg.value, Num(ref n) if !n.is_exact()));
+    Ok((ns, inexact))
+  }
+  fn arity_at_least(args: &[Data], expected: usize, loc: Loc) -> Result<(), InterpreterError> {
+    if args.len() < expected {
+      let kind = ArityMismatch { min: expected, max: None, given: args.len() };
+      return Err(InterpreterError::new(kind, loc));
     }
+    Ok(())
   }
-  pub fn equal(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::boolean(l == r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  fn arity_mismatch(args: &[Data], expected: usize, loc: Loc) -> InterpreterError {
+    Self::arity_range(args, expected, expected, loc)
+  }
+  /// The error of a builtin taking from `min` to `max` arguments.
+  fn arity_range(args: &[Data], min: usize, max: usize, loc: Loc) -> InterpreterError {
+    InterpreterError::new(ArityMismatch { min, max: Some(max), given: args.len() }, loc)
+  }
+  /// The only argument of a builtin taking one.
+  fn one(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let given = args.len();
+    match <[Data; 1]>::try_from(args) {
+      Ok([arg]) => Ok(arg),
+      Err(_) => Err(InterpreterError::new(ArityMismatch { min: 1, max: Some(1), given }, loc)),
     }
   }
-  pub fn lt(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::boolean(l < r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  /// The only argument of a builtin taking one number.
+  fn one_num(args: Vec<Data>, loc: Loc) -> Result<Number, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    Ok(Self::nums(&[arg])?.remove(0))
+  }
+  /// Folds the arguments with `f`, starting from `init`.
+  fn fold(args: Vec<Data>, loc: Loc, init: Number, f: fn(&Number, &Number) -> Number) -> Result<Data, InterpreterError> {
+    let n = Self::nums(&args)?.iter().fold(init, |acc, n| f(&acc, n));
+    Ok(Self::number(n, loc))
+  }
+  /// Folds the arguments with `f`, starting from `init`. An argument for
+  /// which `f` gives `None` is invalid.
+  fn fold_partial(args: Vec<Data>, loc: Loc, init: i32, f: fn(&Number, &Number) -> Option<Number>) -> Result<Data, InterpreterError> {
+    let mut acc = Number::Int(init);
+    for arg in &args {
+      acc = match arg.value {
+        Num(ref n) => f(&acc, n),
+        _ => None,
+      }.ok_or_else(|| InterpreterError::new(InvalidArguments, arg.loc))?;
     }
+    Ok(Self::number(acc, loc))
   }
-  pub fn and(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::num(l & r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
-      }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+  /// Checks that `f` holds for the ordering of every adjacent pair of arguments.
+  /// Any comparison with NaN is false.
+  fn chain(args: Vec<Data>, loc: Loc, f: fn(Ordering) -> bool) -> Result<Data, InterpreterError> {
+    Self::arity_at_least(&args, 1, loc)?;
+    let ns = Self::nums(&args)?;
+    let holds = ns.windows(2).all(|w| w[0].compare(&w[1]).is_some_and(f));
+    Ok(Self::boolean(holds, loc))
+  }
+  /// Applies `f` to the only argument.
+  fn map_num(args: Vec<Data>, loc: Loc, f: fn(&Number) -> Number) -> Result<Data, InterpreterError> {
+    Ok(Self::number(f(&Self::one_num(args, loc)?), loc))
+  }
+  /// Applies `f` to the only argument, which is invalid if `f` gives `None`.
+  fn map_partial(args: Vec<Data>, loc: Loc, f: fn(&Number) -> Option<Number>) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    let n = Self::nums(std::slice::from_ref(&arg))?.remove(0);
+    f(&n).map(|n| Self::number(n, loc))
+      .ok_or_else(|| InterpreterError::new(InvalidArguments, arg.loc))
+  }
+  /// Applies a real function to the only argument, giving an inexact result.
+  fn map_float(args: Vec<Data>, loc: Loc, f: fn(f64) -> f64) -> Result<Data, InterpreterError> {
+    let x = Self::one_num(args, loc)?.to_f64();
+    Ok(Self::number(Number::Float(f(x)), loc))
+  }
+  /// Applies `f` to two integers, the divisor not zero. The result is
+  /// inexact if either argument is.
+  fn divide(args: Vec<Data>, loc: Loc, f: fn(&Number, &Number) -> Number) -> Result<Data, InterpreterError> {
+    let (ns, inexact) = Self::integers(&args)?;
+    match ns[..] {
+      [_, ref d] if d.is_zero() => Err(InterpreterError::new(DivisionByZero, args[1].loc)),
+      [ref n, ref d] => {
+        let q = f(n, d);
+        Ok(Self::number(if inexact { q.to_inexact() } else { q }, loc))
+      },
+      _ => Err(Self::arity_mismatch(&args, 2, loc)),
     }
   }
-  pub fn or(args: Vec<Data>) -> Result<Data, InterpreterError> {
-    if args.len() == 2 {
-      let loc = args[0].loc;
-      match (&args[0].value, &&args[1].value) {
-        (Num(l), Num(r)) => Ok(Self::num(l | r, loc)),
-        _ => Err(Annot::new(InvalidArguments, loc)),
+  /// (+ n...)
+  pub fn add(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::fold(args, loc, Number::Int(0), Number::add)
+  }
+  /// (- n) negates, (- n m...) subtracts from the first.
+  pub fn sub(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::arity_at_least(&args, 1, loc)?;
+    let ns = Self::nums(&args)?;
+    let n = match ns[..] {
+      [ref n] => n.neg(),
+      _ => ns[1..].iter().fold(ns[0].clone(), |acc, m| acc.sub(m)),
+    };
+    Ok(Self::number(n, loc))
+  }
+  /// (* n...)
+  pub fn mul(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::fold(args, loc, Number::Int(1), Number::mul)
+  }
+  /// (/ n) takes the reciprocal, (/ n m...) divides the first.
+  /// Only an exact zero divisor is an error; inexact division may give `+inf.0`.
+  pub fn div(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::arity_at_least(&args, 1, loc)?;
+    let ns = Self::nums(&args)?;
+    let (mut acc, skip) = match ns.len() {
+      1 => (Number::Int(1), 0),
+      _ => (ns[0].clone(), 1),
+    };
+    for (n, arg) in ns.iter().zip(&args).skip(skip) {
+      if n.is_exact_zero() {
+        return Err(InterpreterError::new(DivisionByZero, arg.loc));
       }
-    } else {
-      Err(Annot::new(InvalidArguments, Loc(0, 1)))
+      acc = acc.div(n);
+    }
+    Ok(Self::number(acc, loc))
+  }
+  /// (quotient n d) rounds toward zero.
+  pub fn quotient(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::divide(args, loc, Number::quotient)
+  }
+  /// (remainder n d) has the sign of `n`.
+  pub fn remainder(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::divide(args, loc, Number::remainder)
+  }
+  /// (modulo n d) has the sign of `d`.
+  pub fn modulo(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::divide(args, loc, Number::modulo)
+  }
+  /// (gcd n...)
+  pub fn gcd(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let (ns, inexact) = Self::integers(&args)?;
+    let n = ns.iter().fold(Number::Int(0), |acc, n| acc.gcd(n).unwrap_or(acc));
+    Ok(Self::number(if inexact { n.to_inexact() } else { n }, loc))
+  }
+  /// (lcm n...)
+  pub fn lcm(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let (ns, inexact) = Self::integers(&args)?;
+    let n = ns.iter().fold(Number::Int(1), |acc, n| match acc.gcd(n) {
+      _ if n.is_zero() || acc.is_zero() => Number::Int(0),
+      Some(gcd) => acc.mul(n).abs().quotient(&gcd),
+      None => acc,
+    });
+    Ok(Self::number(if inexact { n.to_inexact() } else { n }, loc))
+  }
+  pub fn gt(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::chain(args, loc, Ordering::is_gt)
+  }
+  pub fn equal(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::chain(args, loc, Ordering::is_eq)
+  }
+  pub fn lt(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::chain(args, loc, Ordering::is_lt)
+  }
+  pub fn le(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::chain(args, loc, Ordering::is_le)
+  }
+  pub fn ge(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::chain(args, loc, Ordering::is_ge)
+  }
+  pub fn bitwise_and(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::fold_partial(args, loc, -1, Number::and)
+  }
+  pub fn bitwise_or(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::fold_partial(args, loc, 0, Number::or)
+  }
+  /// (number? obj)
+  pub fn is_number(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    Ok(Self::boolean(matches!(arg.value, Num(_)), loc))
+  }
+  /// (integer? obj) is true for inexact integers such as `2.0` too.
+  pub fn is_integer(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    Ok(Self::boolean(matches!(arg.value, Num(ref n) if n.is_integer()), loc))
+  }
+  /// (exact? n)
+  pub fn is_exact(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::boolean(Self::one_num(args, loc)?.is_exact(), loc))
+  }
+  /// (inexact? n)
+  pub fn is_inexact(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::boolean(!Self::one_num(args, loc)?.is_exact(), loc))
+  }
+  /// (inexact n), also known as (exact->inexact n)
+  pub fn inexact(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::to_inexact)
+  }
+  /// (exact n), also known as (inexact->exact n), gives the exact rational
+  /// equal to `n`, so `(exact 0.1)` is not `1/10`.
+  pub fn exact(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_partial(args, loc, Number::to_exact)
+  }
+  /// (numerator q)
+  pub fn numerator(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_partial(args, loc, Number::numerator)
+  }
+  /// (denominator q)
+  pub fn denominator(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_partial(args, loc, Number::denominator)
+  }
+  /// (rationalize x y) is the simplest rational within `y` of `x`.
+  pub fn rationalize(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    match Self::nums(&args)?[..] {
+      [ref x, ref y] => x.rationalize(y)
+        .map(|n| Self::number(n, loc))
+        .ok_or_else(|| InterpreterError::new(InvalidArguments, loc)),
+      _ => Err(Self::arity_mismatch(&args, 2, loc)),
+    }
+  }
+  /// (abs n)
+  pub fn abs(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::abs)
+  }
+  /// (min n m...) is inexact if any argument is.
+  pub fn min(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::extremum(args, loc, Ordering::Less)
+  }
+  /// (max n m...) is inexact if any argument is.
+  pub fn max(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::extremum(args, loc, Ordering::Greater)
+  }
+  fn extremum(args: Vec<Data>, loc: Loc, wanted: Ordering) -> Result<Data, InterpreterError> {
+    Self::arity_at_least(&args, 1, loc)?;
+    let ns = Self::nums(&args)?;
+    let best = ns[1..].iter().fold(&ns[0], |best, n| match n.compare(best) {
+      Some(ordering) if ordering == wanted => n,
+      // NaN wins, like any other arithmetic on it
+      None if n.to_f64().is_nan() => n,
+      _ => best,
+    });
+    let exact = ns.iter().all(Number::is_exact);
+    Ok(Self::number(if exact { best.clone() } else { best.to_inexact() }, loc))
+  }
+  /// (floor n)
+  pub fn floor(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::floor)
+  }
+  /// (ceiling n)
+  pub fn ceiling(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::ceiling)
+  }
+  /// (truncate n)
+  pub fn truncate(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::truncate)
+  }
+  /// (round n) rounds ties to even.
+  pub fn round(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::round)
+  }
+  /// (sqrt n) is exact for exact perfect squares.
+  pub fn sqrt(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_num(args, loc, Number::sqrt)
+  }
+  /// (exact-integer-sqrt n) returns the list `(s r)` where `n = s * s + r`.
+  pub fn exact_integer_sqrt(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    let root = match arg.value {
+      Num(ref n) => n.exact_integer_sqrt(),
+      _ => None,
+    };
+    match root {
+      Some((s, r)) => Ok(Self::list(vec![Self::number(s, loc), Self::number(r, loc)], loc)),
+      None => Err(InterpreterError::new(InvalidArguments, arg.loc)),
+    }
+  }
+  /// (expt base exponent) is exact for an exact base and integer exponent.
+  pub fn expt(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    match Self::nums(&args)?[..] {
+      [ref base, ref exponent] if base.is_exact_zero() && exponent.is_exact() && exponent.is_negative() => {
+        Err(InterpreterError::new(DivisionByZero, args[0].loc))
+      },
+      [ref base, ref exponent] => base.pow(exponent)
+        .map(|n| Self::number(n, loc))
+        .ok_or_else(|| InterpreterError::new(Overflow, loc)),
+      _ => Err(Self::arity_mismatch(&args, 2, loc)),
     }
   }
-}
\ No newline at end of file
+  /// (exp n)
+  pub fn exp(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_float(args, loc, f64::exp)
+  }
+  /// (log n)
+  pub fn log(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_float(args, loc, f64::ln)
+  }
+  /// (sin n)
+  pub fn sin(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_float(args, loc, f64::sin)
+  }
+  /// (cos n)
+  pub fn cos(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_float(args, loc, f64::cos)
+  }
+  /// (tan n)
+  pub fn tan(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_float(args, loc, f64::tan)
+  }
+  /// (atan n)
+  pub fn atan(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::map_float(args, loc, f64::atan)
+  }
+  /// (not obj) is true only for `#f`.
+  pub fn not(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::boolean(!Self::one(args, loc)?.is_truthy(), loc))
+  }
+  /// (boolean? obj)
+  pub fn is_boolean(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    Ok(Self::boolean(matches!(arg.value, Boolean(_)), loc))
+  }
+  /// (boolean=? b1 b2 b3...) requires every argument to be a boolean.
+  pub fn boolean_eq(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Self::arity_at_least(&args, 2, loc)?;
+    let bs = args.iter()
+      .map(|arg| match arg.value {
+        Boolean(b) => Ok(b),
+        _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(Self::boolean(bs.windows(2).all(|w| w[0] == w[1]), loc))
+  }
+  /// (char? obj)
+  pub fn is_char(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    Ok(Self::boolean(matches!(arg.value, Char(_)), loc))
+  }
+  /// (char->integer c) is the Unicode code point of `c`.
+  pub fn char_to_integer(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::num(Self::one_char(args, loc)? as i32, loc))
+  }
+  /// (integer->char n) requires `n` to be a Unicode scalar value, so not a surrogate.
+  pub fn integer_to_char(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    let c = Self::index(&arg).ok()
+      .and_then(|n| std::char::from_u32(n as u32))
+      .ok_or_else(|| InterpreterError::new(InvalidArguments, arg.loc))?;
+    Ok(Self::character(c, loc))
+  }
+  /// (char-alphabetic? c) is true for any Unicode letter.
+  pub fn is_char_alphabetic(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::boolean(Self::one_char(args, loc)?.is_alphabetic(), loc))
+  }
+  /// (char-numeric? c) is true for any Unicode numeric character, not just `0`-`9`.
+  pub fn is_char_numeric(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::boolean(Self::one_char(args, loc)?.is_numeric(), loc))
+  }
+  /// (char-whitespace? c) is true for any Unicode white space.
+  pub fn is_char_whitespace(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::boolean(Self::one_char(args, loc)?.is_whitespace(), loc))
+  }
+  /// (char-upcase c) leaves `c` alone when its uppercase is not a single
+  /// character, as for `#\ß`; `string-upcase` handles those.
+  pub fn char_upcase(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let c = Self::one_char(args, loc)?;
+    let mut upper = c.to_uppercase();
+    let c = match (upper.next(), upper.next()) {
+      (Some(u), None) => u,
+      _ => c,
+    };
+    Ok(Self::character(c, loc))
+  }
+  /// (string? obj)
+  pub fn is_string(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    Ok(Self::boolean(matches!(arg.value, String(_)), loc))
+  }
+  /// (string-length s) counts characters, not bytes.
+  pub fn string_length(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    let s = Self::strs(std::slice::from_ref(&arg))?.remove(0);
+    Ok(Self::num(s.chars().count() as i32, loc))
+  }
+  /// (string-ref s k) is the `k`th character of `s`.
+  pub fn string_ref(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    match args[..] {
+      [ref s, ref k] => {
+        let c = Self::strs(std::slice::from_ref(s))?[0].chars().nth(Self::index(k)?)
+          .ok_or_else(|| InterpreterError::new(InvalidArguments, k.loc))?;
+        Ok(Self::character(c, loc))
+      },
+      _ => Err(Self::arity_mismatch(&args, 2, loc)),
+    }
+  }
+  /// (substring s start [end]) takes the characters from `start` up to but
+  /// not including `end`, which defaults to the length of `s`.
+  pub fn substring(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let (s, start, end) = match args[..] {
+      [ref s, ref start] => (s, start, None),
+      [ref s, ref start, ref end] => (s, start, Some(end)),
+      _ => return Err(Self::arity_range(&args, 2, 3, loc)),
+    };
+    let s = Self::strs(std::slice::from_ref(s))?.remove(0);
+    let len = s.chars().count();
+    let end_index = match end {
+      Some(end) => Some(Self::index(end)?).filter(|&i| i <= len)
+        .ok_or_else(|| InterpreterError::new(InvalidArguments, end.loc))?,
+      None => len,
+    };
+    let start_index = Some(Self::index(start)?).filter(|&i| i <= end_index)
+      .ok_or_else(|| InterpreterError::new(InvalidArguments, start.loc))?;
+    let sub = s.chars().skip(start_index).take(end_index - start_index).collect::<std::string::String>();
+    Ok(Self::string(sub, loc))
+  }
+  /// (string-append s...)
+  pub fn string_append(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    Ok(Self::string(Self::strs(&args)?.concat(), loc))
+  }
+  /// (string-split s [sep]) splits at every occurrence of `sep`, or at runs
+  /// of whitespace without one.
+  pub fn string_split(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let strs = Self::strs(&args)?;
+    let parts = match strs[..] {
+      [ref s] => s.split_whitespace().map(|part| Self::string(part, loc)).collect(),
+      [_, ref sep] if sep.is_empty() => return Err(InterpreterError::new(InvalidArguments, args[1].loc)),
+      [ref s, ref sep] => s.split(&**sep).map(|part| Self::string(part, loc)).collect(),
+      _ => return Err(Self::arity_range(&args, 1, 2, loc)),
+    };
+    Ok(Self::list(parts, loc))
+  }
+  /// (string-join strings [sep]) joins a list of strings with `sep`, a space by default.
+  pub fn string_join(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let (list, sep) = match args[..] {
+      [ref list] => (list, Rc::from(" ")),
+      [ref list, ref sep] => (list, Self::strs(std::slice::from_ref(sep))?.remove(0)),
+      _ => return Err(Self::arity_range(&args, 1, 2, loc)),
+    };
+    let items = Self::items(list).ok_or_else(|| InterpreterError::new(InvalidArguments, list.loc))?;
+    Ok(Self::string(Self::strs(&items)?.join(&*sep), loc))
+  }
+  /// The radix argument of a number conversion: 2, 8, 10 or 16.
+  fn radix(arg: Option<&Data>) -> Result<u32, InterpreterError> {
+    let arg = match arg {
+      Some(arg) => arg,
+      None => return Ok(10),
+    };
+    match arg.value {
+      Num(Number::Int(radix @ (2 | 8 | 10 | 16))) => Ok(radix as u32),
+      _ => Err(InterpreterError::new(InvalidArguments, arg.loc)),
+    }
+  }
+  /// (string->number s [radix]) is `#f` if `s` is not a number literal.
+  pub fn string_to_number(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    if args.is_empty() || args.len() > 2 {
+      return Err(Self::arity_range(&args, 1, 2, loc));
+    }
+    let s = Self::strs(&args[..1])?.remove(0);
+    let radix = Self::radix(args.get(1))?;
+    Ok(match super::lexer::parse_number(&s, radix) {
+      Some(n) => Self::number(n, loc),
+      None => Self::boolean(false, loc),
+    })
+  }
+  /// (number->string n [radix]); only exact numbers can be shown in a radix other than 10.
+  pub fn number_to_string(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    if args.is_empty() || args.len() > 2 {
+      return Err(Self::arity_range(&args, 1, 2, loc));
+    }
+    let n = Self::nums(&args[..1])?.remove(0);
+    let radix = Self::radix(args.get(1))?;
+    let s = n.to_string_radix(radix).ok_or_else(|| InterpreterError::new(InvalidArguments, args[0].loc))?;
+    Ok(Self::string(s, loc))
+  }
+  /// (string-upcase s) follows Unicode case mapping, so "ß" becomes "SS".
+  pub fn string_upcase(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    let arg = Self::one(args, loc)?;
+    let s = Self::strs(std::slice::from_ref(&arg))?.remove(0);
+    Ok(Self::string(s.to_uppercase(), loc))
+  }
+  /// (display obj) shows strings without quotes.
+  pub fn display(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    match &args[..] {
+      [obj] => {
+        print!("{}", obj);
+        Ok(Self::unspecified(loc))
+      },
+      _ => Err(Self::arity_mismatch(&args, 1, loc)),
+    }
+  }
+  /// (write obj) shows strings quoted and escaped, so that they read back.
+  pub fn write(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    match &args[..] {
+      [obj] => {
+        print!("{:#}", obj);
+        Ok(Self::unspecified(loc))
+      },
+      _ => Err(Self::arity_mismatch(&args, 1, loc)),
+    }
+  }
+  /// (newline)
+  pub fn newline(args: Vec<Data>, loc: Loc) -> Result<Data, InterpreterError> {
+    match &args[..] {
+      [] => {
+        println!();
+        Ok(Self::unspecified(loc))
+      },
+      _ => Err(Self::arity_mismatch(&args, 0, loc)),
+    }
+  }
+}
@@ -1,10 +1,11 @@
 use super::Loc;
 use super::token::TokenKind;
-use super::lexer::LexError;
+use super::lexer::{char_name, LexError};
 use super::parser::ParseError;
-use super::error::Error;
+use super::error::{Error, ReadError, Severity};
 use super::data::Data;
-use super::interpreter::InterpreterError;
+use super::number::Number;
+use super::interpreter::{Frame, InterpreterError};
 use std::fmt;
 
 impl fmt::Display for TokenKind {
@@ -12,17 +13,12 @@ impl fmt::Display for TokenKind {
     use self::TokenKind::*;
     match self {
       Number(n) => n.fmt(f),
-      Plus => write!(f, "+"),
-      Minus => write!(f, "-"),
-      Asterisk => write!(f, "*"),
-      Slash => write!(f, "/"),
-      Less => write!(f, "<"),
-      Equal => write!(f, "="),
-      And => write!(f, "&"),
-      Or => write!(f, "|"),
+      Boolean(b) => write!(f, "{}", if *b { "#t" } else { "#f" }),
+      Symbol(name) => write!(f, "{}", name),
+      String(s) => write_string(f, s),
+      Char(c) => write_char(f, *c),
       Quote => write!(f, "'"),
-      dot => write!(f, "."),
-      Greater => write!(f, ">"),
+      Dot => write!(f, "."),
       LParen => write!(f, "("),
       RParen => write!(f, ")"),
       // LBrace => write!(f, "{{"),
@@ -33,6 +29,20 @@ impl fmt::Display for TokenKind {
   }
 }
 
+/// Floats always show a fraction or an exponent so that they read back as floats.
+impl fmt::Display for Number {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Number::Int(n) => write!(f, "{}", n),
+      Number::Big(ref n) => write!(f, "{}", n.to_decimal()),
+      Number::Ratio(ref r) => write!(f, "{}/{}", r.numer().to_decimal(), r.denom().to_decimal()),
+      Number::Float(x) if x.is_nan() => write!(f, "+nan.0"),
+      Number::Float(x) if x.is_infinite() => write!(f, "{}inf.0", if x > 0.0 { "+" } else { "-" }),
+      Number::Float(x) => write!(f, "{:?}", x),
+    }
+  }
+}
+
 impl fmt::Display for Loc {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{}-{}", self.0, self.1)
@@ -42,10 +52,13 @@ impl fmt::Display for Loc {
 impl fmt::Display for LexError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use super::lexer::LexErrorKind::*;
-    let loc = &self.loc;
     match self.value {
-      InvalidChar(c) => write!(f, "{}: invalid char '{}'", loc, c),
-      Eof => write!(f, "End of file"),
+      InvalidChar(c) => write!(f, "invalid character '{}'", c),
+      InvalidNumber => write!(f, "invalid number literal"),
+      UnterminatedString => write!(f, "unterminated string"),
+      InvalidEscape => write!(f, "invalid escape sequence"),
+      UnknownCharName => write!(f, "unknown character name"),
+      Eof => write!(f, "unexpected end of input"),
     }
   }
 }
@@ -54,49 +67,156 @@ impl fmt::Display for ParseError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use self::ParseError::*;
     match self {
-      UnexpectedToken(tok) => write!(f, "{}: {} is not expected", tok.loc, tok.value),
-      NotExpression(tok) => write!(
-        f,
-        "{}: '{}' is not a start of expression",
-        tok.loc, tok.value
-      ),
-      NotOperator(tok) => write!(f, "{}: '{}' is not an operator", tok.loc, tok.value),
-      UnclosedOpenParen(tok) => write!(f, "{}: '{}' is not closed", tok.loc, tok.value),
-      RedundantExpression(tok) => write!(
-        f,
-        "{}: expression after '{}' is redundant",
-        tok.loc, tok.value
-      ),
-      Eof => write!(f, "End of file"),
+      UnexpectedToken(tok) => write!(f, "unexpected '{}'", tok.value),
+      NotExpression(tok) => write!(f, "'{}' cannot start an expression", tok.value),
+      UnclosedOpenParen(tok) => write!(f, "unclosed '{}'", tok.value),
+      TooDeep(_) => write!(f, "expression nested too deeply"),
+      Eof => write!(f, "unexpected end of input"),
     }
   }
 }
 
+impl fmt::Display for ReadError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "cannot read '{}': {}", self.path, self.message)
+  }
+}
+
 impl fmt::Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "parser error")
+    use self::Error::*;
+    write!(f, "error[{}]: ", self.code())?;
+    match self {
+      Read(e) => e.fmt(f),
+      Lexer(e) => e.fmt(f),
+      Parser(e) => e.fmt(f),
+      Interpreter(e) => e.fmt(f),
+    }
+  }
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Severity::Error => write!(f, "error"),
+    }
   }
 }
 
 impl fmt::Display for InterpreterError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use super::interpreter::InterpreterErrorKind::*;
-    match self.value {
+    match *self.kind() {
       InvalidArguments => write!(f, "invalid arguments"),
-	    DivisionByZero   => write!(f, "division by zero"),
-      CarNotApplicable => write!(f, "car not applicable"),
+      DivisionByZero   => write!(f, "division by zero"),
+      Overflow         => write!(f, "exact result too large"),
+      CarNotApplicable => write!(f, "not a procedure"),
+      UnboundVariable(ref name) => write!(f, "unbound variable '{}'", name),
+      InvalidSyntax    => write!(f, "invalid syntax"),
+      RecursionTooDeep => write!(f, "recursion too deep"),
+      ArityMismatch { min, max, given } => {
+        let last = match max {
+          None => { write!(f, "expected at least {}", min)?; min },
+          Some(max) if max == min => { write!(f, "expected {}", min)?; min },
+          Some(max) => { write!(f, "expected {} to {}", min, max)?; max },
+        };
+        write!(f, " argument{}, but {} given", if last == 1 { "" } else { "s" }, given)
+      },
     }
   }
 }
 
+impl fmt::Display for Frame {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} ({})", self.name(), self.loc())
+  }
+}
+
+/// A string literal, quoted and with escapes that read back as the same string.
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\t' => write!(f, "\\t")?,
+      c if c.is_control() => write!(f, "\\x{:x};", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  write!(f, "\"")
+}
+
+/// A character literal, by name or code point if it is not printable.
+fn write_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+  match char_name(c) {
+    Some(name) => write!(f, "#\\{}", name),
+    None if c.is_control() => write!(f, "#\\x{:x}", c as u32),
+    None => write!(f, "#\\{}", c),
+  }
+}
+
+/// `{}` shows a value as `display` does and `{:#}` as `write` does, which
+/// differ in that `write` quotes strings and writes characters as literals.
+/// Lists are shown from an explicit stack, as they may nest too deeply to
+/// recurse.
 impl fmt::Display for Data {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use super::data::DataKind::*;
-    match self.value {
-      Num(n) => write!(f, "{}", n),
-      Boolean(b) => write!(f, "{}", b),
-      Nil => write!(f, "()"),
-      Symbol(ref name) => write!(f, "{}", name),
+    enum Piece<'a> {
+      Data(&'a Data),
+      Text(&'static str),
+    }
+    let write = f.alternate();
+    let mut pieces = vec![Piece::Data(self)];
+    while let Some(piece) = pieces.pop() {
+      let data = match piece {
+        Piece::Data(data) => data,
+        Piece::Text(text) => {
+          f.write_str(text)?;
+          continue;
+        },
+      };
+      match data.value {
+        Num(ref n) => write!(f, "{}", n)?,
+        Boolean(b) => write!(f, "{}", if b { "#t" } else { "#f" })?,
+        Nil => write!(f, "()")?,
+        Unspecified => write!(f, "#<unspecified>")?,
+        Symbol(ref name) => write!(f, "{}", name)?,
+        String(ref s) if write => write_string(f, s)?,
+        String(ref s) => write!(f, "{}", s)?,
+        Char(c) if write => write_char(f, c)?,
+        Char(c) => write!(f, "{}", c)?,
+        Pair { ref car, ref cdr } => {
+          write!(f, "(")?;
+          let mut list = vec![Piece::Data(car)];
+          let mut rest = cdr;
+          loop {
+            match rest.value {
+              Pair { ref car, ref cdr } => {
+                list.push(Piece::Text(" "));
+                list.push(Piece::Data(car));
+                rest = cdr;
+              },
+              Nil => break,
+              _ => {
+                list.push(Piece::Text(" . "));
+                list.push(Piece::Data(rest));
+                break;
+              },
+            }
+          }
+          list.push(Piece::Text(")"));
+          pieces.extend(list.into_iter().rev());
+        },
+        Builtin(ref builtin) => write!(f, "#<procedure {}>", builtin.name)?,
+        Closure(ref closure) => match closure.name {
+          Some(ref name) => write!(f, "#<procedure {}>", name)?,
+          None => write!(f, "#<procedure>")?,
+        },
+      }
     }
+    Ok(())
   }
 }
\ No newline at end of file
@@ -0,0 +1,533 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+const HISTORY_LIMIT: usize = 1000;
+
+/// A line editor for the REPL with history, reverse search, completion and
+/// paren matching. It puts the terminal into raw mode on Linux and falls back
+/// to plain buffered line reading when stdin is not a terminal or the
+/// platform's terminal interface is not known.
+pub struct Editor {
+  interactive: bool,
+  history: Vec<String>,
+  history_path: Option<PathBuf>,
+  /// Entries in the history file, which is trimmed once there are more
+  /// than `HISTORY_LIMIT`.
+  history_file_len: usize,
+  words: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+  Char(char),
+  Ctrl(char),
+  Enter,
+  Tab,
+  Backspace,
+  Delete,
+  Left,
+  Right,
+  Up,
+  Down,
+  Home,
+  End,
+  Esc,
+  Unknown,
+}
+
+/// The line being edited.
+struct Line<'a> {
+  prompt: &'a str,
+  buf: Vec<char>,
+  pos: usize,
+}
+
+impl Editor {
+  /// Loads history from `history_path` when running on a terminal.
+  pub fn new(history_path: Option<PathBuf>) -> Self {
+    let interactive = raw::is_terminal();
+    let history_path = if interactive { history_path } else { None };
+    let mut history = history_path.as_ref()
+      .and_then(|path| std::fs::read_to_string(path).ok())
+      .map(|s| s.lines().filter(|line| !line.is_empty()).map(String::from).collect::<Vec<_>>())
+      .unwrap_or_default();
+    let history_file_len = history.len();
+    let skip = history.len().saturating_sub(HISTORY_LIMIT);
+    history.drain(..skip);
+    Editor { interactive, history, history_path, history_file_len, words: Vec::new() }
+  }
+
+  /// `~/.rlisp_history`, if the home directory is known.
+  pub fn default_history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rlisp_history"))
+  }
+
+  /// Sets the words offered by tab completion.
+  pub fn set_completions(&mut self, words: Vec<String>) {
+    self.words = words;
+  }
+
+  /// Records `line` in memory and appends it to the history file, rewriting
+  /// the file with the kept entries once it has grown too long.
+  pub fn add_history(&mut self, line: &str) {
+    if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+      return;
+    }
+    self.history.push(line.to_string());
+    if self.history.len() > HISTORY_LIMIT {
+      self.history.remove(0);
+    }
+    let path = match self.history_path {
+      Some(ref path) => path,
+      None => return,
+    };
+    if self.history_file_len >= HISTORY_LIMIT {
+      let mut text = self.history.join("\n");
+      text.push('\n');
+      if std::fs::write(path, text).is_ok() {
+        self.history_file_len = self.history.len();
+      }
+    } else if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+      if writeln!(file, "{}", line).is_ok() {
+        self.history_file_len += 1;
+      }
+    }
+  }
+
+  /// Reads a line without its terminator. Returns `None` at end of input and
+  /// an `Interrupted` error when the user presses Ctrl-C.
+  pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+    if !self.interactive {
+      return read_plain(prompt);
+    }
+    match raw::RawMode::enable() {
+      Some(_raw) => self.edit(prompt),
+      // the terminal refused raw mode
+      None => read_plain(prompt),
+    }
+  }
+
+  fn edit(&mut self, prompt: &str) -> io::Result<Option<String>> {
+    let mut line = Line { prompt, buf: Vec::new(), pos: 0 };
+    // `index == history.len()` is the line being typed, saved in `current`
+    // while browsing older entries
+    let mut index = self.history.len();
+    let mut current = Vec::new();
+    line.refresh()?;
+    loop {
+      let key = match read_key()? {
+        Some(key) => key,
+        None if line.buf.is_empty() => return Ok(None),
+        None => Key::Enter,
+      };
+      match key {
+        Key::Enter => {
+          line.pos = line.buf.len();
+          line.refresh_plain()?;
+          write_str("\r\n")?;
+          return Ok(Some(line.text()));
+        },
+        Key::Ctrl('d') if line.buf.is_empty() => {
+          write_str("\r\n")?;
+          return Ok(None);
+        },
+        Key::Ctrl('c') => {
+          write_str("^C\r\n")?;
+          return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        },
+        Key::Ctrl('d') | Key::Delete if line.pos < line.buf.len() => {
+          line.buf.remove(line.pos);
+        },
+        Key::Backspace | Key::Ctrl('h') if line.pos > 0 => {
+          line.pos -= 1;
+          line.buf.remove(line.pos);
+        },
+        Key::Left | Key::Ctrl('b') => line.pos = line.pos.saturating_sub(1),
+        Key::Right | Key::Ctrl('f') => line.pos = (line.pos + 1).min(line.buf.len()),
+        Key::Home | Key::Ctrl('a') => line.pos = 0,
+        Key::End | Key::Ctrl('e') => line.pos = line.buf.len(),
+        Key::Ctrl('k') => line.buf.truncate(line.pos),
+        Key::Ctrl('u') => {
+          line.buf.drain(..line.pos);
+          line.pos = 0;
+        },
+        Key::Ctrl('w') => {
+          let start = line.word_start();
+          let start = if start == line.pos { line.pos.saturating_sub(1) } else { start };
+          line.buf.drain(start..line.pos);
+          line.pos = start;
+        },
+        Key::Ctrl('l') => write_str("\x1b[H\x1b[2J")?,
+        Key::Up | Key::Ctrl('p') if index > 0 => {
+          if index == self.history.len() {
+            current = line.buf.clone();
+          }
+          index -= 1;
+          line.set(self.history[index].chars().collect());
+        },
+        Key::Down | Key::Ctrl('n') if index < self.history.len() => {
+          index += 1;
+          let buf = match self.history.get(index) {
+            Some(entry) => entry.chars().collect(),
+            None => current.clone(),
+          };
+          line.set(buf);
+        },
+        Key::Tab => self.complete(&mut line)?,
+        Key::Ctrl('r') => {
+          let submit = self.search(&mut line)?;
+          if submit {
+            line.refresh_plain()?;
+            write_str("\r\n")?;
+            return Ok(Some(line.text()));
+          }
+        },
+        Key::Char(c) => {
+          line.buf.insert(line.pos, c);
+          line.pos += 1;
+        },
+        _ => {},
+      }
+      line.refresh()?;
+    }
+  }
+
+  /// Completes the word before the cursor, listing the candidates when the
+  /// common prefix cannot be extended.
+  fn complete(&self, line: &mut Line) -> io::Result<()> {
+    let start = line.word_start();
+    let prefix = line.buf[start..line.pos].iter().collect::<String>();
+    let mut matches = self.words.iter()
+      .filter(|word| word.starts_with(&prefix))
+      .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+    let common = match matches.split_first() {
+      Some((first, rest)) => rest.iter().fold(first.as_str(), |common, word| {
+        let len = common.chars().zip(word.chars())
+          .take_while(|(l, r)| l == r)
+          .map(|(c, _)| c.len_utf8())
+          .sum();
+        &common[..len]
+      }),
+      None => return write_str("\x07"),
+    };
+    if common.len() > prefix.len() {
+      for c in common[prefix.len()..].chars() {
+        line.buf.insert(line.pos, c);
+        line.pos += 1;
+      }
+    } else if matches.len() > 1 {
+      let list = matches.iter().map(|word| word.as_str()).collect::<Vec<_>>().join("  ");
+      write_str(&format!("\r\n{}\r\n", list))?;
+    }
+    Ok(())
+  }
+
+  /// Incremental reverse search through the history (Ctrl-R). Leaves the
+  /// match in `line` and returns whether it should be submitted right away.
+  fn search(&self, line: &mut Line) -> io::Result<bool> {
+    let original = line.buf.clone();
+    let mut query = String::new();
+    let mut found: Option<usize> = None;
+    loop {
+      let entry = found.map_or("", |i| self.history[i].as_str());
+      write_str(&format!("\r(reverse-i-search)`{}': {}\x1b[K", query, entry))?;
+      let key = read_key()?.unwrap_or(Key::Ctrl('g'));
+      let from = match key {
+        Key::Char(c) => {
+          query.push(c);
+          found.map_or(self.history.len(), |i| i + 1)
+        },
+        Key::Backspace | Key::Ctrl('h') => {
+          query.pop();
+          self.history.len()
+        },
+        Key::Ctrl('r') => found.unwrap_or(self.history.len()),
+        Key::Ctrl('g') | Key::Ctrl('c') => {
+          line.set(original);
+          return Ok(false);
+        },
+        Key::Enter => {
+          line.set(entry.chars().collect());
+          return Ok(true);
+        },
+        _ => {
+          line.set(entry.chars().collect());
+          return Ok(false);
+        },
+      };
+      if let Some(i) = self.history[..from].iter().rposition(|entry| entry.contains(&query)) {
+        found = Some(i);
+      }
+    }
+  }
+}
+
+impl<'a> Line<'a> {
+  fn text(&self) -> String {
+    self.buf.iter().collect()
+  }
+
+  fn set(&mut self, buf: Vec<char>) {
+    self.pos = buf.len();
+    self.buf = buf;
+  }
+
+  /// Start of the symbol under construction before the cursor.
+  fn word_start(&self) -> usize {
+    self.buf[..self.pos].iter()
+      .rposition(|&c| c.is_whitespace() || "()'\"".contains(c))
+      .map_or(0, |i| i + 1)
+  }
+
+  /// The paren matching the one just before the cursor, or under it.
+  fn matching_paren(&self) -> Option<usize> {
+    let (at, open) = match (self.pos.checked_sub(1).map(|i| self.buf[i]), self.buf.get(self.pos)) {
+      (Some(')'), _) => (self.pos - 1, false),
+      (_, Some('(')) => (self.pos, true),
+      (_, Some(')')) => (self.pos, false),
+      _ => return None,
+    };
+    let mut depth = 0usize;
+    let step = |i: usize| if open { i.checked_add(1) } else { i.checked_sub(1) };
+    let mut i = Some(at);
+    while let Some(j) = i {
+      match self.buf.get(j)? {
+        '(' if open => depth += 1,
+        ')' if !open => depth += 1,
+        '(' | ')' => {
+          depth -= 1;
+          if depth == 0 {
+            return Some(j);
+          }
+        },
+        _ => {},
+      }
+      i = step(j);
+    }
+    None
+  }
+
+  fn refresh(&self) -> io::Result<()> {
+    self.draw(self.matching_paren())
+  }
+
+  /// Redraws without highlighting, so no highlight is left behind on submit.
+  fn refresh_plain(&self) -> io::Result<()> {
+    self.draw(None)
+  }
+
+  fn draw(&self, highlight: Option<usize>) -> io::Result<()> {
+    let mut out = String::from("\r");
+    out.push_str(self.prompt);
+    for (i, &c) in self.buf.iter().enumerate() {
+      if Some(i) == highlight {
+        out.push_str("\x1b[1;7m");
+        out.push(c);
+        out.push_str("\x1b[0m");
+      } else {
+        out.push(c);
+      }
+    }
+    out.push_str("\x1b[K\r");
+    let column = self.prompt.chars().count() + self.pos;
+    if column > 0 {
+      out.push_str(&format!("\x1b[{}C", column));
+    }
+    write_str(&out)
+  }
+}
+
+fn write_str(s: &str) -> io::Result<()> {
+  let stdout = io::stdout();
+  let mut stdout = stdout.lock();
+  stdout.write_all(s.as_bytes())?;
+  stdout.flush()
+}
+
+fn read_plain(prompt: &str) -> io::Result<Option<String>> {
+  write_str(prompt)?;
+  let mut line = String::new();
+  if io::stdin().read_line(&mut line)? == 0 {
+    return Ok(None);
+  }
+  while line.ends_with('\n') || line.ends_with('\r') {
+    line.pop();
+  }
+  Ok(Some(line))
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+  let mut b = [0u8];
+  match io::stdin().read(&mut b)? {
+    0 => Ok(None),
+    _ => Ok(Some(b[0])),
+  }
+}
+
+fn read_key() -> io::Result<Option<Key>> {
+  let b = match read_byte()? {
+    Some(b) => b,
+    None => return Ok(None),
+  };
+  let key = match b {
+    b'\r' | b'\n' => Key::Enter,
+    b'\t' => Key::Tab,
+    127 => Key::Backspace,
+    0x1b => read_escape()?,
+    1..=26 => Key::Ctrl((b'a' + b - 1) as char),
+    0x20..=0x7e => Key::Char(b as char),
+    0xc0..=0xf7 => {
+      // a UTF-8 lead byte followed by its continuation bytes
+      let len = if b >= 0xf0 { 4 } else if b >= 0xe0 { 3 } else { 2 };
+      let mut bytes = vec![b];
+      for _ in 1..len {
+        bytes.extend(read_byte()?);
+      }
+      match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+        Some(c) => Key::Char(c),
+        None => Key::Unknown,
+      }
+    },
+    _ => Key::Unknown,
+  };
+  Ok(Some(key))
+}
+
+/// Decodes the rest of an escape sequence for the arrow and editing keys.
+fn read_escape() -> io::Result<Key> {
+  let key = match read_byte()? {
+    Some(b'[') => {
+      let mut params = Vec::new();
+      loop {
+        match read_byte()? {
+          Some(b @ 0x40..=0x7e) => break match (&params[..], b) {
+            ([], b'A') => Key::Up,
+            ([], b'B') => Key::Down,
+            ([], b'C') => Key::Right,
+            ([], b'D') => Key::Left,
+            ([], b'H') | (b"1", b'~') | (b"7", b'~') => Key::Home,
+            ([], b'F') | (b"4", b'~') | (b"8", b'~') => Key::End,
+            (b"3", b'~') => Key::Delete,
+            _ => Key::Unknown,
+          },
+          Some(b) => params.push(b),
+          None => break Key::Unknown,
+        }
+      }
+    },
+    Some(b'O') => match read_byte()? {
+      Some(b'A') => Key::Up,
+      Some(b'B') => Key::Down,
+      Some(b'C') => Key::Right,
+      Some(b'D') => Key::Left,
+      Some(b'H') => Key::Home,
+      Some(b'F') => Key::End,
+      _ => Key::Unknown,
+    },
+    _ => Key::Esc,
+  };
+  Ok(key)
+}
+
+// the termios layout and constants below are those of the architectures
+// using the generic Linux definitions
+#[cfg(all(
+  target_os = "linux",
+  any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64"),
+))]
+mod raw {
+  use std::io::IsTerminal;
+  use std::os::raw::{c_int, c_uchar, c_uint};
+
+  /// `struct termios` as laid out by glibc and musl.
+  #[repr(C)]
+  #[derive(Clone, Copy)]
+  struct Termios {
+    c_iflag: c_uint,
+    c_oflag: c_uint,
+    c_cflag: c_uint,
+    c_lflag: c_uint,
+    c_line: c_uchar,
+    c_cc: [c_uchar; 32],
+    c_ispeed: c_uint,
+    c_ospeed: c_uint,
+  }
+
+  extern "C" {
+    fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+    fn tcsetattr(fd: c_int, action: c_int, termios: *const Termios) -> c_int;
+  }
+
+  const ICRNL: c_uint = 0o400;
+  const IXON: c_uint = 0o2000;
+  const ISIG: c_uint = 0o1;
+  const ICANON: c_uint = 0o2;
+  const ECHO: c_uint = 0o10;
+  const IEXTEN: c_uint = 0o100000;
+  const VTIME: usize = 5;
+  const VMIN: usize = 6;
+  const TCSAFLUSH: c_int = 2;
+
+  pub fn is_terminal() -> bool {
+    let dumb = std::env::var("TERM").map_or(true, |term| term == "dumb");
+    !dumb && std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+  }
+
+  /// Raw mode on stdin, restored when dropped.
+  pub struct RawMode(Termios);
+
+  impl RawMode {
+    pub fn enable() -> Option<RawMode> {
+      // SAFETY: the termios struct matches the C layout and outlives the calls
+      unsafe {
+        let mut original = std::mem::zeroed::<Termios>();
+        if tcgetattr(0, &mut original) != 0 {
+          return None;
+        }
+        let mut raw = original;
+        raw.c_iflag &= !(ICRNL | IXON);
+        raw.c_lflag &= !(ECHO | ICANON | ISIG | IEXTEN);
+        raw.c_cc[VMIN] = 1;
+        raw.c_cc[VTIME] = 0;
+        if tcsetattr(0, TCSAFLUSH, &raw) != 0 {
+          return None;
+        }
+        Some(RawMode(original))
+      }
+    }
+  }
+
+  impl Drop for RawMode {
+    fn drop(&mut self) {
+      // SAFETY: restores the settings read in `enable`
+      unsafe {
+        tcsetattr(0, TCSAFLUSH, &self.0);
+      }
+    }
+  }
+}
+
+/// Other targets have no raw mode: the REPL reads plain lines there, with
+/// neither line editing nor history.
+#[cfg(not(all(
+  target_os = "linux",
+  any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64"),
+)))]
+mod raw {
+  /// Always false, so that `Editor::read_line` goes straight to `read_plain`.
+  pub fn is_terminal() -> bool {
+    false
+  }
+
+  pub struct RawMode;
+
+  impl RawMode {
+    /// Never called, as no terminal is ever detected.
+    pub fn enable() -> Option<RawMode> {
+      None
+    }
+  }
+}
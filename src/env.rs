@@ -0,0 +1,62 @@
+use super::data::{Data, DataKind};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type EnvRef = Rc<RefCell<Env>>;
+
+/// A frame of variable bindings, chained to the frame it was created in.
+#[derive(Default)]
+pub struct Env {
+  vars: HashMap<Box<str>, Data>,
+  parent: Option<EnvRef>,
+}
+
+impl Env {
+  pub fn global() -> EnvRef {
+    Rc::new(RefCell::new(Env::default()))
+  }
+  pub fn extend(parent: &EnvRef) -> EnvRef {
+    Rc::new(RefCell::new(Env {
+      vars: HashMap::new(),
+      parent: Some(parent.clone()),
+    }))
+  }
+  /// Binds `name`. A procedure defined here, as by an internal `define`,
+  /// `letrec` or a named `let`, is kept without a reference cycle.
+  pub fn define(&mut self, name: &str, value: Data) {
+    let value = match value.value {
+      DataKind::Closure(ref closure) if closure.captures(self) => Data::closure(closure.downgrade(), value.loc),
+      _ => value,
+    };
+    self.vars.insert(Box::from(name), value);
+  }
+  pub fn lookup(&self, name: &str) -> Option<Data> {
+    match self.vars.get(name) {
+      Some(value) => Some(upgrade(value)),
+      None => self.parent.as_ref().and_then(|parent| parent.borrow().lookup(name)),
+    }
+  }
+  /// Names visible from this frame, including those of enclosing frames.
+  pub fn names(&self) -> Vec<Box<str>> {
+    let mut names = self.parent.as_ref().map_or_else(Vec::new, |parent| parent.borrow().names());
+    names.extend(self.vars.keys().cloned());
+    names
+  }
+  /// Bindings of this frame only, sorted by name.
+  pub fn bindings(&self) -> Vec<(Box<str>, Data)> {
+    let mut bindings = self.vars.iter()
+      .map(|(name, value)| (name.clone(), upgrade(value)))
+      .collect::<Vec<_>>();
+    bindings.sort_by(|l, r| l.0.cmp(&r.0));
+    bindings
+  }
+}
+
+/// A bound value as handed out, holding a procedure's environment strongly.
+fn upgrade(value: &Data) -> Data {
+  match value.value {
+    DataKind::Closure(ref closure) if closure.is_weak() => Data::closure(closure.upgrade(), value.loc),
+    _ => value.clone(),
+  }
+}
@@ -1,26 +1,43 @@
 use super::Loc;
 use super::token::Token;
-use super::lexer::{LexError, lex};
+use super::lexer::{LexError, lex_at};
 use super::ast::Ast;
-use super::parser::{ParseError, parse};
-use std::str::FromStr;
+use super::parser::{ParseError, MAX_NESTING, parse_program_recovering};
 use std::error::Error as StdError;
-use super::interpreter::InterpreterError;
+use super::interpreter::{Frame, InterpreterError};
+use super::source::{Position, SourceFile, SourceMap};
 
+/// A source file that could not be read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReadError {
+  pub path: String,
+  pub message: String,
+}
+
+/// Reads the source file at `path`.
+pub fn read_file(path: &str) -> Result<String, Error> {
+  std::fs::read_to_string(path)
+    .map_err(|e| Error::Read(ReadError { path: String::from(path), message: e.to_string() }))
+}
 
-impl FromStr for Ast {
-  type Err = Error;
-  fn from_str(s: &str) -> Result<Self, Self::Err> {
-    let tokens = lex(s)?;
-    let ast = parse(tokens)?;
-    Ok(ast)
+/// Registers `input` in `sources` under `name`, then lexes and parses it so
+/// that every `Loc` points into the source map. Reports every parse error
+/// found rather than only the first.
+pub fn parse_file(sources: &mut SourceMap, name: &str, input: &str) -> Result<Vec<Ast>, Vec<Error>> {
+  let base = sources.add(name, input);
+  let tokens = lex_at(input, base).map_err(|e| vec![Error::from(e)])?;
+  match parse_program_recovering(tokens) {
+    (program, ref errors) if errors.is_empty() => Ok(program),
+    (_, errors) => Err(errors.into_iter().map(Error::from).collect()),
   }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Error {
+  Read(ReadError),
   Lexer(LexError),
   Parser(ParseError),
+  Interpreter(InterpreterError),
 }
 
 impl From<LexError> for Error {
@@ -35,64 +52,293 @@ impl From<ParseError> for Error {
   }
 }
 
+impl From<InterpreterError> for Error {
+  fn from(e: InterpreterError) -> Self {
+    Error::Interpreter(e)
+  }
+}
+
+impl StdError for ReadError {}
+
 impl StdError for LexError {}
 
 impl StdError for ParseError {}
 
+impl StdError for InterpreterError {}
+
 impl StdError for Error {
   fn source(&self) -> Option<&(dyn StdError + 'static)> {
     use self::Error::*;
     match self {
+      Read(read) => Some(read),
       Lexer(lex) => Some(lex),
       Parser(parse) => Some(parse),
+      Interpreter(interp) => Some(interp),
     }
   }
 }
 
-pub fn print_annot(input: &str, loc: Loc) {
-  eprintln!("{}", input);
-  eprintln!("{}{}", " ".repeat(loc.0), "^".repeat(loc.1 - loc.0));
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+  Error,
+}
+
+/// A span pointed at in addition to the primary one, with a short explanation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+  pub loc: Loc,
+  pub message: String,
+}
+
+/// Everything needed to report an `Error`, independent of how it is rendered.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+  pub code: &'static str,
+  pub severity: Severity,
+  pub message: String,
+  /// The primary span, absent for errors outside any source text.
+  pub loc: Option<Loc>,
+  pub labels: Vec<Label>,
+  pub notes: Vec<String>,
+  pub trace: Vec<Frame>,
+}
+
+impl Label {
+  pub fn new(loc: Loc, message: impl Into<String>) -> Self {
+    Label { loc, message: message.into() }
+  }
 }
 
 impl Error {
-  pub fn show_diagnostic(&self, input: &str) {
+  /// A stable identifier: `E00xx` for I/O, `E01xx` for lexer, `E02xx` for parser and
+  /// `E03xx` for runtime errors.
+  pub fn code(&self) -> &'static str {
+    use self::Error::*;
+    use super::lexer::LexErrorKind as L;
+    use super::interpreter::InterpreterErrorKind as I;
+    match self {
+      Read(_) => "E0001",
+      Lexer(e) => match e.value {
+        L::InvalidChar(_) => "E0101",
+        L::Eof => "E0102",
+        L::InvalidNumber => "E0103",
+        L::UnterminatedString => "E0104",
+        L::InvalidEscape => "E0105",
+        L::UnknownCharName => "E0106",
+      },
+      Parser(e) => match e {
+        ParseError::UnexpectedToken(_) => "E0201",
+        ParseError::NotExpression(_) => "E0202",
+        ParseError::UnclosedOpenParen(_) => "E0204",
+        ParseError::TooDeep(_) => "E0205",
+        ParseError::Eof => "E0206",
+      },
+      Interpreter(e) => match e.kind() {
+        I::InvalidArguments => "E0301",
+        I::DivisionByZero => "E0302",
+        I::Overflow => "E0303",
+        I::CarNotApplicable => "E0304",
+        I::UnboundVariable(_) => "E0305",
+        I::InvalidSyntax => "E0306",
+        I::ArityMismatch { .. } => "E0307",
+        I::RecursionTooDeep => "E0308",
+      },
+    }
+  }
+
+  pub fn diagnostic(&self, sources: &SourceMap) -> Diagnostic {
     use self::Error::*;
-    use self::ParseError as P;
-    let (e, loc): (&dyn StdError, Loc) = match self {
-      Lexer(e) => (e, e.loc.clone()),
+    // parse errors are reported right after their file was added
+    let end = sources.last().map_or(0, |file| file.end());
+    let (message, loc, labels, notes, trace) = match self {
+      Read(e) => (e.to_string(), None, Vec::new(), Vec::new(), Vec::new()),
+      Lexer(e) => (e.to_string(), Some(e.loc), Vec::new(), Vec::new(), Vec::new()),
       Parser(e) => {
-        let loc = match e {
-          P::UnexpectedToken(Token { loc, .. })
-          | P::NotExpression(Token { loc, .. })
-          | P::NotOperator(Token { loc, .. })
-          | P::UnclosedOpenParen(Token { loc, .. }) => loc.clone(),
-          P::RedundantExpression(Token { loc, .. }) => Loc(loc.0, input.len()),
-          P::Eof => Loc(input.len(), input.len() + 1),
+        use self::ParseError::*;
+        let (loc, note) = match e {
+          UnexpectedToken(Token { loc, .. })
+          | NotExpression(Token { loc, .. }) => (*loc, None),
+          UnclosedOpenParen(Token { loc, .. }) => (*loc, Some("the list is still open at the end of input".to_string())),
+          TooDeep(Token { loc, .. }) => (*loc, Some(format!("lists and quotes may nest at most {} deep", MAX_NESTING))),
+          Eof => (Loc(end, end + 1), Some("expected an expression".to_string())),
         };
-        (e, loc)
-      }
+        (e.to_string(), Some(loc), Vec::new(), note.into_iter().collect(), Vec::new())
+      },
+      Interpreter(e) => (e.to_string(), Some(e.loc()), e.labels().to_vec(), e.notes().to_vec(), e.trace().to_vec()),
     };
-    eprintln!("{}", e);
-    print_annot(input, loc);
+    Diagnostic { code: self.code(), severity: Severity::Error, message, loc, labels, notes, trace }
+  }
+
+  /// Prints the diagnostic for this error to stderr.
+  pub fn show_diagnostic(&self, sources: &SourceMap) {
+    eprint!("{}", self.diagnostic(sources).render(sources));
   }
 }
 
-pub fn show_trace<E: StdError>(e: E) {
-  eprintln!("{}", e);
-  let mut source = e.source();
-  while let Some(e) = source {
-    eprintln!("caused by {}", e);
-    source = e.source()
+impl Diagnostic {
+  /// Renders the diagnostic in the style of rustc: a header, the primary span
+  /// and any secondary labels with their source lines, then notes and the call stack.
+  pub fn render(&self, sources: &SourceMap) -> String {
+    use std::fmt::Write;
+    let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+    let width = self.loc.into_iter()
+      .chain(self.labels.iter().map(|label| label.loc))
+      .filter_map(|loc| span(sources, loc))
+      .map(|(_, _, end)| end.line.to_string().len())
+      .max()
+      .unwrap_or(1);
+    let pad = " ".repeat(width);
+    if let Some(loc) = self.loc {
+      render_snippet(&mut out, sources, loc, width, "-->", '^', "");
+    }
+    for label in &self.labels {
+      render_snippet(&mut out, sources, label.loc, width, ":::", '-', &label.message);
+    }
+    if !self.notes.is_empty() || !self.trace.is_empty() {
+      let _ = writeln!(out, "{} |", pad);
+    }
+    for note in &self.notes {
+      let _ = writeln!(out, "{} = note: {}", pad, note);
+    }
+    // a runaway recursion would otherwise list thousands of identical frames
+    const MAX_FRAMES: usize = 20;
+    for frame in self.trace.iter().take(MAX_FRAMES) {
+      let _ = match sources.lookup(frame.loc()) {
+        Some((file, pos)) => writeln!(out, "{} = in {} at {}:{}:{}", pad, frame.name(), file.name(), pos.line, pos.column),
+        None => writeln!(out, "{} = in {}", pad, frame.name()),
+      };
+      if frame.elided() > 0 {
+        let _ = writeln!(out, "{} = ... {} tail call{}", pad, frame.elided(), if frame.elided() == 1 { "" } else { "s" });
+      }
+    }
+    if self.trace.len() > MAX_FRAMES {
+      let _ = writeln!(out, "{} = ... and {} more", pad, self.trace.len() - MAX_FRAMES);
+    }
+    out
   }
 }
 
-impl StdError for InterpreterError {
-  fn description(&self) -> &str {
-    use super::interpreter::InterpreterErrorKind::*;
-    match self.value {
-      InvalidArguments => "invalid arguments",
-	    DivisionByZero   => "division by zero",
-      CarNotApplicable => "car not applicable",
+impl Diagnostic {
+  /// Serializes the diagnostic as a single-line JSON object. Spans are byte
+  /// offsets into their file; lines and columns are 1-based, end columns exclusive.
+  pub fn to_json(&self, sources: &SourceMap) -> String {
+    let mut out = String::from("{");
+    out.push_str("\"code\":");
+    json_string(&mut out, self.code);
+    out.push_str(",\"severity\":");
+    json_string(&mut out, &self.severity.to_string());
+    out.push_str(",\"message\":");
+    json_string(&mut out, &self.message);
+    if let Some(loc) = self.loc {
+      json_span(&mut out, sources, loc);
+    }
+    out.push_str(",\"labels\":[");
+    for (i, label) in self.labels.iter().enumerate() {
+      out.push_str(if i == 0 { "{" } else { ",{" });
+      out.push_str("\"message\":");
+      json_string(&mut out, &label.message);
+      json_span(&mut out, sources, label.loc);
+      out.push('}');
+    }
+    out.push_str("],\"notes\":[");
+    for (i, note) in self.notes.iter().enumerate() {
+      if i > 0 {
+        out.push(',');
+      }
+      json_string(&mut out, note);
+    }
+    out.push_str("],\"frames\":[");
+    for (i, frame) in self.trace.iter().enumerate() {
+      out.push_str(if i == 0 { "{" } else { ",{" });
+      out.push_str("\"name\":");
+      json_string(&mut out, frame.name());
+      json_span(&mut out, sources, frame.loc());
+      out.push_str(",\"elided\":");
+      out.push_str(&frame.elided().to_string());
+      out.push('}');
     }
+    out.push_str("]}");
+    out
   }
-}
\ No newline at end of file
+}
+
+/// Appends `s` as a JSON string literal.
+fn json_string(out: &mut String, s: &str) {
+  use std::fmt::Write;
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => {
+        let _ = write!(out, "\\u{:04x}", c as u32);
+      },
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+/// Appends the fields locating `loc`, preceded by a comma: the file name, the
+/// byte span within it and the start and end positions. Nothing is written
+/// for locations outside the source map, such as those of builtins.
+fn json_span(out: &mut String, sources: &SourceMap, loc: Loc) {
+  use std::fmt::Write;
+  let (file, start) = match sources.lookup(loc) {
+    Some(found) => found,
+    None => return,
+  };
+  let end = file.position(loc.1);
+  out.push_str(",\"file\":");
+  json_string(out, file.name());
+  let _ = write!(
+    out,
+    ",\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{}",
+    loc.0 - file.start(),
+    loc.1.min(file.end()) - file.start(),
+    start.line,
+    start.column,
+    end.line,
+    end.column
+  );
+}
+
+/// The file containing `loc` and the positions of its first and last character.
+fn span(sources: &SourceMap, loc: Loc) -> Option<(&SourceFile, Position, Position)> {
+  let (file, start) = sources.lookup(loc)?;
+  let end = file.position(loc.1.max(loc.0 + 1) - 1);
+  Some((file, start, end))
+}
+
+/// Writes the file position of `loc` followed by the lines it spans, with
+/// line numbers in a gutter and `mark`s under the spanned text.
+fn render_snippet(out: &mut String, sources: &SourceMap, loc: Loc, width: usize, arrow: &str, mark: char, label: &str) {
+  use std::fmt::Write;
+  let (file, start, end) = match span(sources, loc) {
+    Some(found) => found,
+    None => return,
+  };
+  let pad = " ".repeat(width);
+  let _ = writeln!(out, "{}{} {}:{}:{}", pad, arrow, file.name(), start.line, start.column);
+  let _ = writeln!(out, "{} |", pad);
+  let last = end.line.min(file.line_count());
+  for line in start.line..=last {
+    let text = file.line(line);
+    let from = if line == start.line { start.column - 1 } else { 0 };
+    let to = if line == end.line { end.column } else { text.chars().count() };
+    // keep tabs so that the marks line up with the text above
+    let indent = text.chars().take(from)
+      .map(|c| if c == '\t' { '\t' } else { ' ' })
+      .collect::<String>();
+    let marks = mark.to_string().repeat(to.saturating_sub(from).max(1));
+    let _ = writeln!(out, "{:>width$} | {}", line, text, width = width);
+    let _ = if line == last && !label.is_empty() {
+      writeln!(out, "{} | {}{} {}", pad, indent, marks, label)
+    } else {
+      writeln!(out, "{} | {}{}", pad, indent, marks)
+    };
+  }
+}
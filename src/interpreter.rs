@@ -1,102 +1,792 @@
-use super::Annot;
+use super::Loc;
 use super::ast::Ast;
-use super::data::Data;
-use super::error::print_annot;
+use super::data::{BuiltinFn, Closure, Data};
+use super::env::{Env, EnvRef};
+use super::error::Label;
+use std::rc::Rc;
 
-pub struct Interpreter;
+pub struct Interpreter {
+  global: EnvRef,
+  /// Number of nested `eval_in` calls currently running.
+  depth: usize,
+}
+
+/// Nesting depth of evaluation at which `RecursionTooDeep` is raised rather
+/// than overflowing the stack. Each level takes up to about 6 KiB of stack in
+/// an unoptimized build and a quarter of that in a release build.
+const MAX_DEPTH: usize = 1000;
+
+/// Stack size a thread running an `Interpreter` needs to reach `MAX_DEPTH`:
+/// the usual size of a main thread's stack.
+pub const STACK_SIZE: usize = 8 << 20;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InterpreterErrorKind {
   InvalidArguments,
   DivisionByZero,
+  Overflow,
   CarNotApplicable,
+  UnboundVariable(Box<str>),
+  InvalidSyntax,
+  /// `max` is `None` if any number of arguments from `min` on is accepted.
+  ArityMismatch { min: usize, max: Option<usize>, given: usize },
+  RecursionTooDeep,
+}
+
+/// Boxed, so that results passed up the nested evaluation stay small.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InterpreterError(Box<ErrorInfo>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ErrorInfo {
+  value: InterpreterErrorKind,
+  loc: Loc,
+  trace: Vec<Frame>,
+  labels: Vec<Label>,
+  notes: Vec<String>,
+}
+
+/// An active procedure application: the procedure's name, if it has one,
+/// and the call site.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+  name: Option<Rc<str>>,
+  loc: Loc,
+  /// Tail calls made between the enclosing frame and this one, whose own
+  /// frames were not kept.
+  elided: usize,
+}
+
+/// The outcome of a single evaluation step: either a finished value, or an
+/// expression in tail position still to be evaluated in the given environment.
+/// A tail expression coming from a procedure body carries the frame of the call.
+enum Step {
+  Value(Data),
+  Tail(Ast, EnvRef, Option<Frame>),
 }
 
-pub type InterpreterError = Annot<InterpreterErrorKind>;
+/// Names evaluated by `Interpreter` itself rather than looked up in the environment.
+pub const SPECIAL_FORMS: &[&str] = &[
+  "define", "lambda", "if", "cond", "when", "unless", "and", "or", "begin", "let", "let*", "letrec",
+];
+
+const BUILTINS: &[(&str, BuiltinFn)] = &[
+  ("+", Data::add),
+  ("-", Data::sub),
+  ("*", Data::mul),
+  ("/", Data::div),
+  ("<", Data::lt),
+  ("<=", Data::le),
+  ("=", Data::equal),
+  (">", Data::gt),
+  (">=", Data::ge),
+  ("bitwise-and", Data::bitwise_and),
+  ("bitwise-ior", Data::bitwise_or),
+  ("number?", Data::is_number),
+  ("integer?", Data::is_integer),
+  ("exact?", Data::is_exact),
+  ("inexact?", Data::is_inexact),
+  ("exact", Data::exact),
+  ("inexact", Data::inexact),
+  ("exact->inexact", Data::inexact),
+  ("inexact->exact", Data::exact),
+  ("numerator", Data::numerator),
+  ("denominator", Data::denominator),
+  ("rationalize", Data::rationalize),
+  ("quotient", Data::quotient),
+  ("remainder", Data::remainder),
+  ("modulo", Data::modulo),
+  ("gcd", Data::gcd),
+  ("lcm", Data::lcm),
+  ("abs", Data::abs),
+  ("min", Data::min),
+  ("max", Data::max),
+  ("floor", Data::floor),
+  ("ceiling", Data::ceiling),
+  ("truncate", Data::truncate),
+  ("round", Data::round),
+  ("sqrt", Data::sqrt),
+  ("exact-integer-sqrt", Data::exact_integer_sqrt),
+  ("expt", Data::expt),
+  ("exp", Data::exp),
+  ("log", Data::log),
+  ("sin", Data::sin),
+  ("cos", Data::cos),
+  ("tan", Data::tan),
+  ("atan", Data::atan),
+  ("not", Data::not),
+  ("boolean?", Data::is_boolean),
+  ("boolean=?", Data::boolean_eq),
+  ("char?", Data::is_char),
+  ("char->integer", Data::char_to_integer),
+  ("integer->char", Data::integer_to_char),
+  ("char-alphabetic?", Data::is_char_alphabetic),
+  ("char-numeric?", Data::is_char_numeric),
+  ("char-whitespace?", Data::is_char_whitespace),
+  ("char-upcase", Data::char_upcase),
+  ("string?", Data::is_string),
+  ("string-length", Data::string_length),
+  ("string-ref", Data::string_ref),
+  ("substring", Data::substring),
+  ("string-append", Data::string_append),
+  ("string-split", Data::string_split),
+  ("string-join", Data::string_join),
+  ("string->number", Data::string_to_number),
+  ("number->string", Data::number_to_string),
+  ("string-upcase", Data::string_upcase),
+  ("display", Data::display),
+  ("write", Data::write),
+  ("newline", Data::newline),
+];
 
 impl Interpreter {
   pub fn new() -> Self {
-    Interpreter
+    let global = Env::global();
+    for &(name, f) in BUILTINS {
+      global.borrow_mut().define(name, Data::builtin(name, f, Loc(0, 0)));
+    }
+    Interpreter { global, depth: 0 }
+  }
+
+  /// Global bindings, sorted by name.
+  pub fn globals(&self) -> Vec<(Box<str>, Data)> {
+    self.global.borrow().bindings()
+  }
+
+  /// Binds `command-line` to a procedure returning `args` as a list.
+  pub fn set_command_line(&mut self, args: &[String]) {
+    let args = args.iter().map(|arg| Data::string(arg.as_str(), Loc(0, 0))).collect();
+    let args = Data::list(args, Loc(0, 0));
+    let command_line = Data::builtin("command-line", move |rest, loc| match rest.len() {
+      0 => Ok(Data::new(args.value.clone(), loc)),
+      given => Err(InterpreterError::new(InterpreterErrorKind::ArityMismatch { min: 0, max: Some(0), given }, loc)),
+    }, Loc(0, 0));
+    self.global.borrow_mut().define("command-line", command_line);
   }
 
   pub fn eval(&mut self, expr: &Ast) -> Result<Data, InterpreterError> {
+    let env = self.global.clone();
+    self.eval_in(expr, &env)
+  }
+
+  /// Evaluates top-level expressions in order and returns the value of the last one.
+  pub fn eval_program(&mut self, program: &[Ast]) -> Result<Data, InterpreterError> {
+    let mut ret = Data::nil(Loc(0, 0));
+    for expr in program {
+      ret = self.eval(expr)?;
+    }
+    Ok(ret)
+  }
+
+  /// Evaluates `expr`, failing once evaluations nest too deeply.
+  fn eval_in(&mut self, expr: &Ast, env: &EnvRef) -> Result<Data, InterpreterError> {
+    if self.depth == MAX_DEPTH {
+      return Err(recursion_too_deep(expr.loc));
+    }
+    self.depth += 1;
+    let result = self.eval_steps(expr, env);
+    self.depth -= 1;
+    result
+  }
+
+  /// Evaluates `expr`, looping instead of recursing whenever a step leaves an
+  /// expression in tail position, so tail calls run in constant stack space.
+  /// Of a chain of tail calls only the first and the latest frame are kept,
+  /// with a count of those in between.
+  fn eval_steps(&mut self, expr: &Ast, env: &EnvRef) -> Result<Data, InterpreterError> {
+    let mut step = self.step(expr, env)?;
+    let mut first = None;
+    let mut last: Option<Frame> = None;
+    let mut elided = 0;
+    loop {
+      match step {
+        Step::Value(value) => return Ok(value),
+        Step::Tail(expr, env, callee) => {
+          if first.is_none() {
+            first = callee;
+          } else if let Some(callee) = callee {
+            if last.replace(callee).is_some() {
+              elided += 1;
+            }
+          }
+          step = match self.step(&expr, &env) {
+            Ok(step) => step,
+            Err(e) => return Err(with_tail_frames(e, first, last, elided)),
+          };
+        },
+      }
+    }
+  }
+
+  fn step(&mut self, expr: &Ast, env: &EnvRef) -> Result<Step, InterpreterError> {
     use super::ast::AstKind::*;
     match &expr.value {
-      Num(n) => Ok(Data::num(*n as i32, expr.loc)),
-      Nil => Ok(Data::nil(expr.loc)),
-      Op { ref op } => {
-        use super::ast::OpKind::*;
-        match op.value {
-          Add    => Ok(Data::symbol("add", expr.loc)),
-          Sub    => Ok(Data::symbol("sub", expr.loc)),
-          Mul    => Ok(Data::symbol("mul", expr.loc)),
-          Div    => Ok(Data::symbol("div", expr.loc)),
-          Gt     => Ok(Data::symbol("gt", expr.loc)),
-          Equal  => Ok(Data::symbol("equal", expr.loc)),
-          Lt     => Ok(Data::symbol("lt", expr.loc)),
-          And    => Ok(Data::symbol("and", expr.loc)),
-          Or     => Ok(Data::symbol("or", expr.loc)),
-        }
+      Num(n) => Ok(Step::Value(Data::number(n.clone(), expr.loc))),
+      Boolean(b) => Ok(Step::Value(Data::boolean(*b, expr.loc))),
+      String(s) => Ok(Step::Value(Data::string(s.clone(), expr.loc))),
+      Char(c) => Ok(Step::Value(Data::character(*c, expr.loc))),
+      Nil => Ok(Step::Value(Data::nil(expr.loc))),
+      Symbol(name) => match env.borrow().lookup(name) {
+        Some(value) => Ok(Step::Value(Data::new(value.value, expr.loc))),
+        None => {
+          let e = InterpreterError::new(InterpreterErrorKind::UnboundVariable(name.clone()), expr.loc);
+          let mut names = env.borrow().names();
+          names.extend(SPECIAL_FORMS.iter().map(|&form| Box::from(form)));
+          Err(match closest_name(name, names) {
+            Some(similar) => e.with_note(format!("did you mean '{}'?", similar)),
+            None => e,
+          })
+        },
       },
-      Pair  { l, r } => {
-        let car = self.eval(&l)?;
-        use super::data::DataKind::*;
-        match car.value {
-          Symbol(name) => {
-            let args = vec_args(r.clone())?;
-            let args = args.into_iter().map(|arg| self.eval(&*arg))
-              .filter(|arg| arg.is_ok())
-              .map(|arg| arg.unwrap())
-              .collect::<Vec<Data>>();
-            match &*name {
-              "add"   => Data::add(args),
-              "sub"   => Data::sub(args),
-              "mul"   => Data::mul(args),
-              "div"   => Data::div(args),
-              "gt"    => Data::gt(args), 
-              "equal" => Data::equal(args),
-              "lt"    => Data::lt(args), 
-              "and"   => Data::and(args),
-              "or"    => Data::or(args),
-              _       => Err(InterpreterError::new(InterpreterErrorKind::CarNotApplicable, expr.loc)),
-            }
-          },
-          _ => Err(InterpreterError::new(InterpreterErrorKind::CarNotApplicable, expr.loc)),
+      Pair  { l, r } => match l.value {
+        Symbol(ref name) if SPECIAL_FORMS.contains(&&**name) => self.eval_special_form(name, r, expr.loc, env),
+        _ => self.eval_application(l, r, expr.loc, env),
+      },
+      Quote { q } => Ok(Step::Value(Data::quote(q))),
+      Error => Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, expr.loc)),
+    }
+  }
+
+  /// Evaluates the special form `(name . args)`. Kept apart from `step` so
+  /// that ordinary applications, which nest deeply, use less stack.
+  fn eval_special_form(&mut self, name: &str, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let step = match name {
+      "define" => self.eval_define(args, loc, env),
+      "lambda" => self.eval_lambda(args, loc, env),
+      "if"     => self.eval_if(args, loc, env),
+      "cond"   => self.eval_cond(args, loc, env),
+      "when"   => self.eval_when(args, loc, env, true),
+      "unless" => self.eval_when(args, loc, env, false),
+      "and"    => self.eval_and(args, loc, env),
+      "or"     => self.eval_or(args, loc, env),
+      "begin"  => vec_args(args).and_then(|body| self.eval_body(&body, env, loc)),
+      "let"    => self.eval_let(args, loc, env),
+      "let*"   => self.eval_let_star(args, loc, env),
+      "letrec" => self.eval_letrec(args, loc, env),
+      _ => unreachable!("{} is not a special form", name),
+    };
+    step.map_err(|e| match (e.kind(), syntax_of(name)) {
+      // errors from nested forms already carry their own note
+      (InterpreterErrorKind::InvalidSyntax, Some(syntax)) if e.notes().is_empty() =>
+        e.with_note(format!("expected {}", syntax)),
+      _ => e,
+    })
+  }
+
+  fn eval_application(&mut self, l: &Ast, r: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let car = self.eval_in(l, env)?;
+    let exprs = vec_args(r)?;
+    // a plain loop keeps the stack used per nested call small
+    let mut args = Vec::with_capacity(exprs.len());
+    for arg in exprs {
+      args.push(self.eval_in(arg, env)?);
+    }
+    self.apply(car, args, loc)
+  }
+
+  fn apply(&mut self, car: Data, args: Vec<Data>, loc: Loc) -> Result<Step, InterpreterError> {
+    use super::data::DataKind::*;
+    match car.value {
+      Builtin(ref builtin) => (builtin.f)(args, loc)
+        .map(Step::Value)
+        .map_err(|e| e.with_frame(Frame::new(Some(Rc::from(builtin.name)), loc))),
+      Closure(ref closure) => {
+        let arity = closure.params.len();
+        let max = if closure.rest.is_some() { None } else { Some(arity) };
+        if args.len() < arity || max.is_some_and(|max| args.len() > max) {
+          let kind = InterpreterErrorKind::ArityMismatch { min: arity, max, given: args.len() };
+          return Err(InterpreterError::new(kind, loc).with_label(Label::new(closure.loc, "procedure defined here")));
         }
-      }
-  	  Quote { q } => {
-        match q.value {
-          Num(n)                 => Ok(Data::num(n as i32, expr.loc)),
-          Nil                    => Ok(Data::nil(expr.loc)),
-          Op { ref op }          => Ok(Data::symbol(&format!("{:?}", op), expr.loc)),
-          Pair { ref l, ref r }  => unimplemented!(),
-          Quote { ref q }        => Ok(self.eval(q)?),
+        let env = Env::extend(&closure.env());
+        let mut args = args.into_iter();
+        for (param, arg) in closure.params.iter().zip(args.by_ref()) {
+          env.borrow_mut().define(param, arg);
+        }
+        if let Some(ref rest) = closure.rest {
+          env.borrow_mut().define(rest, Data::list(args.collect(), loc));
         }
+        let frame = Frame::new(closure.name.clone(), loc);
+        match self.eval_body(&closure.body, &env, loc) {
+          Ok(Step::Tail(expr, env, _)) => Ok(Step::Tail(expr, env, Some(frame))),
+          Ok(step) => Ok(step),
+          Err(e) => Err(e.with_frame(frame)),
+        }
+      },
+      _ => {
+        let label = Label::new(car.loc, format!("this evaluates to {:#}", car));
+        Err(InterpreterError::new(InterpreterErrorKind::CarNotApplicable, loc).with_label(label))
+      },
+    }
+  }
+
+  /// Evaluates all but the last expression of a body, leaving the last one in tail position.
+  fn eval_body<A: std::borrow::Borrow<Ast>>(&mut self, body: &[A], env: &EnvRef, loc: Loc) -> Result<Step, InterpreterError> {
+    let (last, init) = body.split_last()
+      .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+    for expr in init {
+      self.eval_in(expr.borrow(), env)?;
+    }
+    Ok(Step::Tail(last.borrow().clone(), env.clone(), None))
+  }
+
+  /// (define name expr)
+  /// (define (name . params) body...)
+  fn eval_define(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    use super::ast::AstKind::*;
+    let invalid_syntax = || InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc);
+    let args = vec_args(args)?;
+    let (target, rest) = args.split_first().ok_or_else(invalid_syntax)?;
+    match &target.value {
+      Symbol(name) => {
+        let value = match rest {
+          [expr] => self.eval_in(expr, env)?,
+          _ => return Err(invalid_syntax()),
+        };
+        env.borrow_mut().define(name, value);
+        Ok(Step::Value(Data::symbol(name, target.loc)))
+      },
+      Pair { l, r } => {
+        let name = match &l.value {
+          Symbol(name) => name,
+          _ => return Err(invalid_syntax()),
+        };
+        let closure = make_closure(Some(Rc::from(&**name)), r, rest, loc, env)?;
+        env.borrow_mut().define(name, Data::closure(closure, loc));
+        Ok(Step::Value(Data::symbol(name, l.loc)))
       },
-	  }
+      _ => Err(invalid_syntax()),
+    }
+  }
+
+  /// (lambda params body...)
+  fn eval_lambda(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (params, body) = args.split_first()
+      .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+    let closure = make_closure(None, params, body, loc, env)?;
+    Ok(Step::Value(Data::closure(closure, loc)))
+  }
+
+  /// (if test consequent alternative?)
+  fn eval_if(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (test, consequent, alternative) = match args[..] {
+      [test, consequent] => (test, consequent, None),
+      [test, consequent, alternative] => (test, consequent, Some(alternative)),
+      _ => return Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc)),
+    };
+    if self.eval_in(test, env)?.is_truthy() {
+      Ok(Step::Tail(consequent.clone(), env.clone(), None))
+    } else {
+      match alternative {
+        Some(alternative) => Ok(Step::Tail(alternative.clone(), env.clone(), None)),
+        None => Ok(Step::Value(Data::nil(loc))),
+      }
+    }
+  }
+
+  /// (cond (test body...)... (else body...)?)
+  fn eval_cond(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    use super::ast::AstKind::*;
+    for clause in vec_args(args)? {
+      let clause = vec_args(clause)?;
+      let (test, body) = clause.split_first()
+        .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+      if let Symbol(ref name) = test.value {
+        if &**name == "else" {
+          return self.eval_body(body, env, test.loc);
+        }
+      }
+      let value = self.eval_in(test, env)?;
+      if value.is_truthy() {
+        return if body.is_empty() { Ok(Step::Value(value)) } else { self.eval_body(body, env, test.loc) };
+      }
+    }
+    Ok(Step::Value(Data::nil(loc)))
+  }
+
+  /// (when test body...)
+  /// (unless test body...)
+  fn eval_when(&mut self, args: &Ast, loc: Loc, env: &EnvRef, expected: bool) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (test, body) = args.split_first()
+      .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+    if self.eval_in(test, env)?.is_truthy() == expected {
+      self.eval_body(body, env, loc)
+    } else {
+      Ok(Step::Value(Data::nil(loc)))
+    }
+  }
+
+  /// (and expr...)
+  fn eval_and(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (last, init) = match args.split_last() {
+      Some(split) => split,
+      None => return Ok(Step::Value(Data::boolean(true, loc))),
+    };
+    for expr in init {
+      let value = self.eval_in(expr, env)?;
+      if !value.is_truthy() {
+        return Ok(Step::Value(value));
+      }
+    }
+    Ok(Step::Tail((*last).clone(), env.clone(), None))
+  }
+
+  /// (or expr...)
+  fn eval_or(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (last, init) = match args.split_last() {
+      Some(split) => split,
+      None => return Ok(Step::Value(Data::boolean(false, loc))),
+    };
+    for expr in init {
+      let value = self.eval_in(expr, env)?;
+      if value.is_truthy() {
+        return Ok(Step::Value(value));
+      }
+    }
+    Ok(Step::Tail((*last).clone(), env.clone(), None))
+  }
+
+  /// (let ((name init)...) body...)
+  /// (let loop ((name init)...) body...)
+  fn eval_let(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    use super::ast::AstKind::*;
+    let args = vec_args(args)?;
+    let (first, rest) = args.split_first()
+      .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+    if let Symbol(ref name) = first.value {
+      let (bindings, body) = rest.split_first()
+        .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+      let bindings = vec_bindings(bindings)?;
+      let params = bindings.iter().rev()
+        .fold(Ast::nil(loc), |params, (param, init)| Ast::pair(Ast::symbol(param, init.loc), params, loc));
+      let inits = bindings.iter()
+        .map(|(_, init)| self.eval_in(init, env))
+        .collect::<Result<Vec<_>, _>>()?;
+      let env = Env::extend(env);
+      let closure = Data::closure(make_closure(Some(Rc::from(&**name)), &params, body, loc, &env)?, loc);
+      env.borrow_mut().define(name, closure.clone());
+      return self.apply(closure, inits, loc);
+    }
+    let bindings = vec_bindings(first)?;
+    let inner = Env::extend(env);
+    for (name, init) in bindings {
+      let value = self.eval_in(init, env)?;
+      inner.borrow_mut().define(name, value);
+    }
+    self.eval_body(rest, &inner, loc)
+  }
+
+  /// (let* ((name init)...) body...)
+  fn eval_let_star(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (bindings, body) = args.split_first()
+      .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+    let mut env = env.clone();
+    for (name, init) in vec_bindings(bindings)? {
+      let value = self.eval_in(init, &env)?;
+      env = Env::extend(&env);
+      env.borrow_mut().define(name, value);
+    }
+    self.eval_body(body, &env, loc)
+  }
+
+  /// (letrec ((name init)...) body...)
+  fn eval_letrec(&mut self, args: &Ast, loc: Loc, env: &EnvRef) -> Result<Step, InterpreterError> {
+    let args = vec_args(args)?;
+    let (bindings, body) = args.split_first()
+      .ok_or_else(|| InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc))?;
+    let env = Env::extend(env);
+    for (name, init) in vec_bindings(bindings)? {
+      let value = self.eval_in(init, &env)?;
+      env.borrow_mut().define(name, value);
+    }
+    self.eval_body(body, &env, loc)
+  }
+}
+
+impl Default for Interpreter {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
 impl InterpreterError {
-  pub fn show_diagnostic(&self, input: &str) {
-    eprintln!("{}", self);
-    print_annot(input, self.loc);
+  pub fn new(value: InterpreterErrorKind, loc: Loc) -> Self {
+    InterpreterError(Box::new(ErrorInfo { value, loc, trace: Vec::new(), labels: Vec::new(), notes: Vec::new() }))
+  }
+  pub fn kind(&self) -> &InterpreterErrorKind {
+    &self.0.value
+  }
+  pub fn loc(&self) -> Loc {
+    self.0.loc
+  }
+  /// Frames of the applications enclosing the failure, innermost first.
+  pub fn trace(&self) -> &[Frame] {
+    &self.0.trace
+  }
+  /// Other spans relevant to the failure, such as a procedure's definition.
+  pub fn labels(&self) -> &[Label] {
+    &self.0.labels
+  }
+  pub fn notes(&self) -> &[String] {
+    &self.0.notes
+  }
+  fn with_frame(mut self, frame: Frame) -> Self {
+    self.0.trace.push(frame);
+    self
+  }
+  fn with_label(mut self, label: Label) -> Self {
+    self.0.labels.push(label);
+    self
+  }
+  fn with_note(mut self, note: String) -> Self {
+    self.0.notes.push(note);
+    self
+  }
+}
+
+impl Frame {
+  fn new(name: Option<Rc<str>>, loc: Loc) -> Self {
+    Frame { name, loc, elided: 0 }
+  }
+  pub fn name(&self) -> &str {
+    self.name.as_deref().unwrap_or("lambda")
+  }
+  pub fn loc(&self) -> Loc {
+    self.loc
+  }
+  /// The number of tail calls left out of the trace just outside this frame.
+  pub fn elided(&self) -> usize {
+    self.elided
+  }
+}
+
+/// Adds the frames kept of a chain of tail calls to `e`, innermost first.
+fn with_tail_frames(mut e: InterpreterError, first: Option<Frame>, last: Option<Frame>, elided: usize) -> InterpreterError {
+  if let Some(last) = last {
+    e = e.with_frame(Frame { elided, ..last });
+  }
+  match first {
+    Some(first) => e.with_frame(first),
+    None => e,
   }
 }
 
-fn vec_args(args: Box<Ast>) -> Result<Vec<Box<Ast>>, InterpreterError>{
+/// The error for evaluation nested deeper than `MAX_DEPTH`.
+fn recursion_too_deep(loc: Loc) -> InterpreterError {
+  let e = InterpreterError::new(InterpreterErrorKind::RecursionTooDeep, loc);
+  e.with_note(format!("evaluation is limited to {} nested calls", MAX_DEPTH))
+}
+
+/// The elements of a proper list; an improper tail such as the `2` in
+/// `(+ 1 . 2)` is invalid syntax.
+fn vec_args(args: &Ast) -> Result<Vec<&Ast>, InterpreterError> {
   let mut args = args;
   let mut vec_args = Vec::with_capacity(4);
   use super::ast::AstKind::*;
   loop {
-    match args.value {
+    match &args.value {
       Pair { l, r } => {
-        vec_args.push(l);
+        vec_args.push(&**l);
         args = r;
       }
-      _ => {
-        return Ok(vec_args);
+      Nil => return Ok(vec_args),
+      _ => return Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, args.loc)),
+    }
+  }
+}
+
+/// Splits `((name init)...)` into its bindings.
+fn vec_bindings(bindings: &Ast) -> Result<Vec<(&str, &Ast)>, InterpreterError> {
+  use super::ast::AstKind::*;
+  vec_args(bindings)?.into_iter()
+    .map(|binding| match vec_args(binding)?[..] {
+      [Ast { value: Symbol(ref name), .. }, init] => Ok((&**name, init)),
+      _ => Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, binding.loc)),
+    })
+    .collect()
+}
+
+/// Builds a closure over `env` from a parameter list such as `(a b)`, `(a b . rest)` or `args`.
+fn make_closure(name: Option<Rc<str>>, params: &Ast, body: &[&Ast], loc: Loc, env: &EnvRef) -> Result<Closure, InterpreterError> {
+  use super::ast::AstKind::*;
+  if body.is_empty() {
+    return Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, loc));
+  }
+  let mut fixed = Vec::new();
+  let mut params = params;
+  let rest = loop {
+    match &params.value {
+      Pair { l, r } => match &l.value {
+        Symbol(param) => {
+          fixed.push(param.clone());
+          params = r;
+        },
+        _ => return Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, l.loc)),
       },
+      Symbol(rest) => break Some(Rc::from(&**rest)),
+      Nil => break None,
+      _ => return Err(InterpreterError::new(InterpreterErrorKind::InvalidSyntax, params.loc)),
+    }
+  };
+  Ok(Closure::new(name, fixed, rest, body.iter().map(|expr| (*expr).clone()).collect(), env, loc))
+}
+
+/// The shape of a special form, as shown when it is used incorrectly.
+fn syntax_of(form: &str) -> Option<&'static str> {
+  match form {
+    "define" => Some("(define name expr) or (define (name . params) body...)"),
+    "lambda" => Some("(lambda params body...)"),
+    "if"     => Some("(if test consequent alternative?)"),
+    "cond"   => Some("(cond (test body...)... (else body...)?)"),
+    "when"   => Some("(when test body...)"),
+    "unless" => Some("(unless test body...)"),
+    "begin"  => Some("(begin body...)"),
+    "let"    => Some("(let ((name init)...) body...) or (let loop ((name init)...) body...)"),
+    "let*"   => Some("(let* ((name init)...) body...)"),
+    "letrec" => Some("(letrec ((name init)...) body...)"),
+    _ => None,
+  }
+}
+
+/// The name in `names` closest to a misspelled `name`, if any is close enough.
+fn closest_name(name: &str, names: Vec<Box<str>>) -> Option<Box<str>> {
+  let len = name.chars().count();
+  let limit = std::cmp::max(1, len / 3);
+  names.into_iter()
+    .map(|candidate| (edit_distance(name, &candidate), candidate))
+    // replacing every character of a short name is not a misspelling
+    .filter(|&(distance, _)| distance <= limit && distance < len)
+    .min()
+    .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, ca) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, &cb) in b.iter().enumerate() {
+      let substitution = diagonal + if ca == cb { 0 } else { 1 };
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
     }
   }
-}
\ No newline at end of file
+  row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::lexer::lex;
+  use super::super::parser::parse_program;
+  use std::rc::Rc;
+
+  fn run(interp: &mut Interpreter, source: &str) -> Result<Data, InterpreterError> {
+    let program = parse_program(lex(source).unwrap()).unwrap();
+    interp.eval_program(&program)
+  }
+
+  #[test]
+  fn procedures_bound_where_they_are_defined_are_freed() {
+    let mut interp = Interpreter::new();
+    run(&mut interp, "(define (g) (let loop ((i 0)) (if (< i 1) (loop (+ i 1)) i)))").unwrap();
+    run(&mut interp, "(define (h) (define (inner) 1) (letrec ((f (lambda () (inner)))) (f)))").unwrap();
+    run(&mut interp, "(define (repeat n) (when (> n 0) (g) (h) (repeat (- n 1))))").unwrap();
+    // every environment leaked by a call would hold on to the global one
+    let before = Rc::strong_count(&interp.global);
+    run(&mut interp, "(repeat 100)").unwrap();
+    assert_eq!(Rc::strong_count(&interp.global), before);
+  }
+
+  #[test]
+  fn improper_lists_are_invalid_syntax() {
+    let mut interp = Interpreter::new();
+    for source in &["(+ 1 . 2)", "(let ((x 1) . 2) x)", "(begin 1 . 2)", "(if 1 1 . 2)"] {
+      let e = run(&mut interp, source).unwrap_err();
+      assert_eq!(e.kind(), &InterpreterErrorKind::InvalidSyntax, "{}", source);
+    }
+  }
+
+  #[test]
+  fn only_close_names_are_suggested() {
+    let names = || vec!["*".into(), "car".into(), "cdr".into(), "display".into()];
+    assert_eq!(closest_name("x", names()), None);
+    assert_eq!(closest_name("ab", names()), None);
+    assert_eq!(closest_name("cat", names()).as_deref(), Some("car"));
+    assert_eq!(closest_name("dsplay", names()).as_deref(), Some("display"));
+  }
+
+  #[test]
+  fn output_procedures_return_an_unspecified_value() {
+    let mut interp = Interpreter::new();
+    assert!(run(&mut interp, "(newline)").unwrap().is_unspecified());
+    assert!(!run(&mut interp, "'()").unwrap().is_unspecified());
+  }
+
+  #[test]
+  fn arity_errors_report_the_accepted_range() {
+    let mut interp = Interpreter::new();
+    let message = |interp: &mut Interpreter, source| run(interp, source).unwrap_err().to_string();
+    assert_eq!(message(&mut interp, "(substring \"abc\")"), "expected 2 to 3 arguments, but 1 given");
+    assert_eq!(message(&mut interp, "(string->number)"), "expected 1 to 2 arguments, but 0 given");
+    assert_eq!(message(&mut interp, "((lambda (x . y) x))"), "expected at least 1 argument, but 0 given");
+    assert_eq!(message(&mut interp, "((lambda (x) x) 1 2)"), "expected 1 argument, but 2 given");
+  }
+
+  #[test]
+  fn tail_calls_run_in_constant_stack() {
+    let mut interp = Interpreter::new();
+    run(&mut interp, "(define (count n) (if (= n 0) 'done (count (- n 1))))").unwrap();
+    let value = run(&mut interp, "(count 1000000)").unwrap();
+    assert_eq!(value.to_string(), "done");
+  }
+
+  #[test]
+  fn deeply_nested_values_are_shown_and_freed() {
+    let mut interp = Interpreter::new();
+    let source = "(define (f . x) x) (define (nest n x) (if (= n 0) x (nest (- n 1) (f x)))) (nest 100000 '())";
+    let shown = run(&mut interp, source).unwrap().to_string();
+    assert_eq!(shown, format!("{}(){}", "(".repeat(100_000), ")".repeat(100_000)));
+  }
+
+  #[test]
+  fn tail_calls_keep_the_first_and_latest_frame() {
+    let mut interp = Interpreter::new();
+    let trace = |interp: &mut Interpreter, source| -> Vec<(String, usize)> {
+      let e = run(interp, source).unwrap_err();
+      e.trace().iter().map(|frame| (frame.name().to_string(), frame.elided())).collect()
+    };
+    run(&mut interp, "(define (h) (g))").unwrap();
+    run(&mut interp, "(define (g) x)").unwrap();
+    let frames = trace(&mut interp, "(h)");
+    assert_eq!(frames, [("g".to_string(), 0), ("h".to_string(), 0)]);
+    run(&mut interp, "(define (c n) (if (= n 0) x (c (- n 1))))").unwrap();
+    run(&mut interp, "(define (top) (+ 1 (c 10)))").unwrap();
+    let frames = trace(&mut interp, "(top)");
+    assert_eq!(frames, [("c".to_string(), 9), ("c".to_string(), 0), ("top".to_string(), 0)]);
+  }
+
+  #[test]
+  fn deep_recursion_is_an_error() {
+    let interpreter = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(|| {
+      let mut interp = Interpreter::new();
+      run(&mut interp, "(define (f n) (if (= n 0) 0 (+ 1 (f (- n 1)))))").unwrap();
+      let e = run(&mut interp, "(f 100000)").unwrap_err();
+      assert_eq!(e.kind(), &InterpreterErrorKind::RecursionTooDeep);
+      // the depth is unwound along with the error
+      assert_eq!(run(&mut interp, "(f 500)").unwrap().to_string(), "500");
+      // nesting through special forms takes more stack per level
+      run(&mut interp, "(define (g n) (cond ((= n 0) 0) (else (let* ((x (g (- n 1)))) (and (> x -1) (+ x 1))))))").unwrap();
+      let e = run(&mut interp, "(g 100000)").unwrap_err();
+      assert_eq!(e.kind(), &InterpreterErrorKind::RecursionTooDeep);
+    });
+    interpreter.unwrap().join().unwrap();
+  }
+}
@@ -1,9 +1,15 @@
 use super::{Loc, Annot};
 use super::token::Token;
+use super::number::Number;
+use super::bigint::BigInt;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LexErrorKind {
   InvalidChar(char),
+  InvalidNumber,
+  UnterminatedString,
+  InvalidEscape,
+  UnknownCharName,
   Eof,
 }
 
@@ -13,6 +19,18 @@ impl LexError {
   pub fn invalid_char(c: char, loc: Loc) -> Self {
     LexError::new(LexErrorKind::InvalidChar(c), loc)
   }
+  pub fn invalid_number(loc: Loc) -> Self {
+    LexError::new(LexErrorKind::InvalidNumber, loc)
+  }
+  pub fn unterminated_string(loc: Loc) -> Self {
+    LexError::new(LexErrorKind::UnterminatedString, loc)
+  }
+  pub fn invalid_escape(loc: Loc) -> Self {
+    LexError::new(LexErrorKind::InvalidEscape, loc)
+  }
+  pub fn unknown_char_name(loc: Loc) -> Self {
+    LexError::new(LexErrorKind::UnknownCharName, loc)
+  }
   pub fn eof(loc: Loc) -> Self {
     LexError::new(LexErrorKind::Eof, loc)
   }
@@ -39,50 +57,273 @@ fn recognize_many(input: &[u8], mut pos: usize, mut f: impl FnMut(u8) -> bool) -
   pos
 }
 
+/// Lexes `42`, `-5`, `+5`, `1_000`, a literal with a radix prefix such as
+/// `#x1F`, `#b101`, `#o17` or `#d10`, or a float such as `1.5`, `1e-3` or
+/// `+inf.0`. Anything else starting with a digit or a sign, such as `1+`,
+/// is an identifier.
 fn lex_number(input: &[u8], pos: usize) -> Result<(Token, usize), LexError> {
+  let start = pos;
+  let end = recognize_many(input, start + 1, is_symbol_byte);
+  let loc = Loc(start, end);
+  let (radix, digits) = match radix_prefix(&input[start..end]) {
+    Some((0, text)) => match parse_float(text) {
+      Some(x) => return Ok((Token::number(Number::Float(x), loc), end)),
+      None => (0, text),
+    },
+    Some(prefixed) => prefixed,
+    None => return Err(LexError::invalid_char('#', Loc(start, start + 1))),
+  };
+  match parse_rational(digits, if radix == 0 { 10 } else { radix }) {
+    Ok(n) => Ok((Token::number(n, loc), end)),
+    // misplaced separators or a zero denominator are a typo rather than an identifier
+    Err(LexErrorKind::InvalidNumber) if radix == 0 && !digits[1..].iter().all(|&b| b.is_ascii_digit() || b == b'_' || b == b'/') =>
+      lex_symbol(input, pos),
+    Err(kind) => Err(LexError::new(kind, loc)),
+  }
+}
+/// Parses all of `text` as a number literal, in `radix` unless it has a
+/// radix prefix. Floats are only read in radix 10.
+pub fn parse_number(text: &str, radix: u32) -> Option<Number> {
+  match radix_prefix(text.as_bytes())? {
+    (0, digits) => match parse_float(digits) {
+      Some(x) if radix == 10 => Some(Number::Float(x)),
+      _ => parse_rational(digits, radix).ok(),
+    },
+    (radix, digits) => parse_rational(digits, radix).ok(),
+  }
+}
+/// Splits a radix prefix such as `#x` off a number literal, giving a radix
+/// of 0 if there is none. `None` for an unknown prefix.
+fn radix_prefix(text: &[u8]) -> Option<(u32, &[u8])> {
+  match text {
+    [b'#', b'x' | b'X', rest @ ..] => Some((16, rest)),
+    [b'#', b'b' | b'B', rest @ ..] => Some((2, rest)),
+    [b'#', b'o' | b'O', rest @ ..] => Some((8, rest)),
+    [b'#', b'd' | b'D', rest @ ..] => Some((10, rest)),
+    [b'#', ..] => None,
+    _ => Some((0, text)),
+  }
+}
+/// Parses a decimal with a fraction or an exponent, or one of `+inf.0`,
+/// `-inf.0` and `+nan.0`.
+fn parse_float(text: &[u8]) -> Option<f64> {
   use std::str::from_utf8;
 
-  let start = pos;
-  let end = recognize_many(input, start, |b| b"1234567890".contains(&b));
-  let n = from_utf8(&input[start..end])
-    .unwrap()
-    .parse()
-    .unwrap();
-  Ok((Token::number(n, Loc(start, end)), end))
+  match text {
+    b"+inf.0" => return Some(f64::INFINITY),
+    b"-inf.0" => return Some(f64::NEG_INFINITY),
+    b"+nan.0" | b"-nan.0" => return Some(f64::NAN),
+    _ => {},
+  }
+  let unsigned = match text {
+    [b'+' | b'-', rest @ ..] => rest,
+    _ => text,
+  };
+  let (mantissa, exponent) = match unsigned.iter().position(|&b| b == b'e' || b == b'E') {
+    Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+    None => (unsigned, None),
+  };
+  let (int, frac) = match mantissa.iter().position(|&b| b == b'.') {
+    Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+    None => (mantissa, None),
+  };
+  let digits = |s: &[u8]| s.iter().all(u8::is_ascii_digit);
+  let valid = digits(int)
+    && frac.is_none_or(digits)
+    && int.len() + frac.map_or(0, <[u8]>::len) > 0
+    && (frac.is_some() || exponent.is_some())
+    && exponent.is_none_or(|e| {
+      let e = e.strip_prefix(b"+").or_else(|| e.strip_prefix(b"-")).unwrap_or(e);
+      !e.is_empty() && digits(e)
+    });
+  if !valid {
+    return None;
+  }
+  from_utf8(text).ok()?.parse().ok()
 }
-fn lex_plus(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'+').map(|(_, end)| (Token::plus(Loc(start, end)), end))
+/// Whether the number literal at `pos` starts with a sign or a dot rather than a digit.
+fn is_signed_number(input: &[u8], pos: usize) -> bool {
+  let rest = &input[pos + 1..];
+  match input[pos] {
+    b'+' | b'-' => rest.first().is_some_and(u8::is_ascii_digit)
+      || rest.starts_with(b"inf.0")
+      || rest.starts_with(b"nan.0")
+      || (rest.first() == Some(&b'.') && rest.get(1).is_some_and(u8::is_ascii_digit)),
+    b'.' => rest.first().is_some_and(u8::is_ascii_digit),
+    _ => false,
+  }
 }
-fn lex_minus(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'-').map(|(_, end)| (Token::minus(Loc(start, end)), end))
+/// Parses an integer or a fraction `n/d` with an unsigned, non-zero denominator.
+fn parse_rational(text: &[u8], radix: u32) -> Result<Number, LexErrorKind> {
+  let i = match text.iter().position(|&b| b == b'/') {
+    Some(i) => i,
+    None => return parse_integer(text, radix),
+  };
+  let (numer, denom) = (&text[..i], &text[i + 1..]);
+  if denom.starts_with(b"+") || denom.starts_with(b"-") {
+    return Err(LexErrorKind::InvalidNumber);
+  }
+  match (parse_integer(numer, radix)?, parse_integer(denom, radix)?) {
+    (_, denom) if denom.is_zero() => Err(LexErrorKind::InvalidNumber),
+    (numer, denom) => Ok(numer.div(&denom)),
+  }
 }
-fn lex_asterisk(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'*').map(|(_, end)| (Token::asterisk(Loc(start, end)), end))
+/// Parses an optionally signed integer, allowing single `_`s between digits.
+/// Integers too large for an `i32` become bignums.
+fn parse_integer(text: &[u8], radix: u32) -> Result<Number, LexErrorKind> {
+  let (negative, digits) = match text {
+    [b'-', rest @ ..] => (true, rest),
+    [b'+', rest @ ..] => (false, rest),
+    _ => (false, text),
+  };
+  if digits.is_empty() || digits.starts_with(b"_") || digits.ends_with(b"_") || digits.windows(2).any(|w| w == b"__") {
+    return Err(LexErrorKind::InvalidNumber);
+  }
+  let digits = digits.iter()
+    .filter(|&&b| b != b'_')
+    .map(|&b| (b as char).to_digit(radix).ok_or(LexErrorKind::InvalidNumber))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(Number::from_big(BigInt::from_digits(negative, digits.into_iter(), radix)))
 }
-fn lex_slash(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'/').map(|(_, end)| (Token::slash(Loc(start, end)), end))
+/// Bytes that may appear in an identifier. Non-ASCII bytes are accepted so
+/// that UTF-8 encoded identifiers are kept whole.
+fn is_symbol_byte(b: u8) -> bool {
+  b.is_ascii_alphanumeric() || b"!$%&*/:<=>?^_~+-.@|".contains(&b) || b >= 0x80
 }
-fn lex_less(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'<').map(|(_, end)| (Token::less(Loc(start, end)), end))
+fn lex_symbol(input: &[u8], pos: usize) -> Result<(Token, usize), LexError> {
+  use std::str::from_utf8;
+
+  let start = pos;
+  let end = recognize_many(input, start, is_symbol_byte);
+  let name = from_utf8(&input[start..end])
+    .map_err(|_| LexError::invalid_char(input[start] as char, Loc(start, end)))?;
+  Ok((Token::symbol(name, Loc(start, end)), end))
 }
-fn lex_equal(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'=').map(|(_, end)| (Token::equal(Loc(start, end)), end))
+/// Lexes a double-quoted string, which may span lines. Besides `\n`, `\t`,
+/// `\\` and `\"`, a character may be escaped by its code point as `\x41;`
+/// or `\u{41}`.
+fn lex_string(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+  let mut bytes = Vec::new();
+  let mut pos = start + 1;
+  loop {
+    match input.get(pos) {
+      None => return Err(LexError::unterminated_string(Loc(start, pos))),
+      Some(b'"') => break,
+      Some(b'\\') => {
+        let (c, end) = lex_escape(input, pos).ok_or_else(|| {
+          let end = recognize_many(input, pos + 1, |b| b.is_ascii_alphanumeric() || b"{};".contains(&b));
+          if end == input.len() {
+            LexError::unterminated_string(Loc(start, end))
+          } else {
+            LexError::invalid_escape(Loc(pos, end.max(pos + 2)))
+          }
+        })?;
+        bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        pos = end;
+      },
+      Some(&b) => {
+        bytes.push(b);
+        pos += 1;
+      },
+    }
+  }
+  // escapes only split the UTF-8 input at ASCII bytes
+  let text = String::from_utf8_lossy(&bytes);
+  Ok((Token::string(&text, Loc(start, pos + 1)), pos + 1))
+}
+/// The character escaped by the backslash at `pos` and the position after the escape.
+fn lex_escape(input: &[u8], pos: usize) -> Option<(char, usize)> {
+  let code_point = |digits: &[u8]| {
+    let digits = std::str::from_utf8(digits).ok()?;
+    std::char::from_u32(u32::from_str_radix(digits, 16).ok()?)
+  };
+  match *input.get(pos + 1)? {
+    b'n' => Some(('\n', pos + 2)),
+    b't' => Some(('\t', pos + 2)),
+    b'\\' => Some(('\\', pos + 2)),
+    b'"' => Some(('"', pos + 2)),
+    b'x' => {
+      let end = recognize_many(input, pos + 2, |b| b.is_ascii_hexdigit());
+      match input.get(end) {
+        Some(b';') => Some((code_point(&input[pos + 2..end])?, end + 1)),
+        _ => None,
+      }
+    },
+    b'u' if input.get(pos + 2) == Some(&b'{') => {
+      let end = recognize_many(input, pos + 3, |b| b.is_ascii_hexdigit());
+      match input.get(end) {
+        Some(b'}') => Some((code_point(&input[pos + 3..end])?, end + 1)),
+        _ => None,
+      }
+    },
+    _ => None,
+  }
 }
-fn lex_greater(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'>').map(|(_, end)| (Token::greater(Loc(start, end)), end))
+/// Lexes `#t`, `#f`, `#true` or `#false`.
+fn lex_boolean(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+  // a following `#` belongs to the token, so `#t#f` is not two booleans
+  let end = recognize_many(input, start + 1, |b| is_symbol_byte(b) || b == b'#');
+  let b = match &input[start..end] {
+    b"#t" | b"#true" => true,
+    b"#f" | b"#false" => false,
+    _ => return Err(LexError::invalid_char('#', Loc(start, start + 1))),
+  };
+  Ok((Token::boolean(b, Loc(start, end)), end))
 }
-fn lex_and(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'&').map(|(_, end)| (Token::and(Loc(start, end)), end))
+/// Names accepted after `#\\`, as in R7RS.
+const CHAR_NAMES: &[(&str, char)] = &[
+  ("alarm", '\x07'),
+  ("backspace", '\x08'),
+  ("delete", '\x7f'),
+  ("escape", '\x1b'),
+  ("newline", '\n'),
+  ("null", '\0'),
+  ("return", '\r'),
+  ("space", ' '),
+  ("tab", '\t'),
+];
+/// The character named `name`, as written after `#\\`.
+pub fn char_by_name(name: &str) -> Option<char> {
+  CHAR_NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, c)| c)
 }
-fn lex_or(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-  consume_byte(input, start, b'|').map(|(_, end)| (Token::or(Loc(start, end)), end))
+/// The name of `c` to write after `#\\`, if it has one.
+pub fn char_name(c: char) -> Option<&'static str> {
+  CHAR_NAMES.iter().find(|&&(_, named)| named == c).map(|&(n, _)| n)
+}
+/// Lexes a character literal: `#\\a`, `#\\λ`, `#\\(`, a name such as
+/// `#\\space`, or a code point such as `#\\x41`.
+fn lex_char(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+  use std::str::from_utf8;
+
+  let pos = start + 2;
+  // the character itself may be any, including a delimiter; only a
+  // letter can start a name
+  let end = match input.get(pos) {
+    Some(b) if b.is_ascii_alphabetic() => recognize_many(input, pos + 1, is_symbol_byte),
+    Some(_) => recognize_many(input, pos + 1, |b| b & 0xc0 == 0x80),
+    None => return Err(LexError::eof(Loc(start, pos))),
+  };
+  let loc = Loc(start, end);
+  let text = from_utf8(&input[pos..end]).map_err(|_| LexError::invalid_char(input[pos] as char, loc))?;
+  let mut chars = text.chars();
+  let c = match (chars.next(), chars.next()) {
+    (Some(c), None) => Some(c),
+    _ => char_by_name(text).or_else(|| {
+      let digits = text.strip_prefix('x')?;
+      std::char::from_u32(u32::from_str_radix(digits, 16).ok()?)
+    }),
+  };
+  match c {
+    Some(c) => Ok((Token::character(c, loc), end)),
+    None => Err(LexError::unknown_char_name(loc)),
+  }
 }
 fn lex_quote(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
   consume_byte(input, start, b'\'').map(|(_, end)| (Token::quote(Loc(start, end)), end))
 }
 fn lex_dot(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
-	consume_byte(input, start, b'.').map(|(_, end)| (Token::dot(Loc(start, end)), end))
-  }
+  consume_byte(input, start, b'.').map(|(_, end)| (Token::dot(Loc(start, end)), end))
+}
 fn lex_lparen(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
   consume_byte(input, start, b'(').map(|(_, end)| (Token::lparen(Loc(start, end)), end))
 }
@@ -102,7 +343,12 @@ fn lex_rparen(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
 //   consume_byte(input, start, b']').map(|(_, end)| (Token::rbracket(Loc(start, end)), end))
 // }
 fn skip_spaces(input: &[u8], pos: usize) -> Result<((), usize), LexError> {
-  let pos = recognize_many(input, pos, |b| b" \n\t".contains(&b));
+  let pos = recognize_many(input, pos, |b| b" \n\t\r".contains(&b));
+  Ok(((), pos))
+}
+/// Skips a `;` comment up to the end of the line.
+fn skip_comment(input: &[u8], pos: usize) -> Result<((), usize), LexError> {
+  let pos = recognize_many(input, pos, |b| b != b'\n');
   Ok(((), pos))
 }
 pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
@@ -118,30 +364,158 @@ pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
   }
   while pos < input.len() {
     match input[pos] {
-      b'0'..=b'9' => lex_a_token!(lex_number(input, pos)),
-      b'+' => lex_a_token!(lex_plus(input, pos)),
-      b'-' => lex_a_token!(lex_minus(input, pos)),
-      b'*' => lex_a_token!(lex_asterisk(input, pos)),
-      b'/' => lex_a_token!(lex_slash(input, pos)),
-      b'<' => lex_a_token!(lex_less(input, pos)),
-      b'=' => lex_a_token!(lex_equal(input, pos)),
-      b'>' => lex_a_token!(lex_greater(input, pos)),
-      b'&' => lex_a_token!(lex_and(input, pos)),
-	  b'|' => lex_a_token!(lex_or(input, pos)),
-	  b'\'' => lex_a_token!(lex_quote(input, pos)),
-	  b'.' => lex_a_token!(lex_dot(input, pos)),
+      b'#' if input.get(pos + 1) == Some(&b'\\') => lex_a_token!(lex_char(input, pos)),
+      b'#' if input.get(pos + 1).is_some_and(|&b| b == b't' || b == b'f') => lex_a_token!(lex_boolean(input, pos)),
+      b'0'..=b'9' | b'#' => lex_a_token!(lex_number(input, pos)),
+      b'+' | b'-' | b'.' if is_signed_number(input, pos) => lex_a_token!(lex_number(input, pos)),
+      b'"' => lex_a_token!(lex_string(input, pos)),
+      b'\'' => lex_a_token!(lex_quote(input, pos)),
+      // a lone '.' is the pair separator, otherwise it starts an identifier such as `...`
+      b'.' if !input.get(pos + 1).is_some_and(|&b| is_symbol_byte(b)) => lex_a_token!(lex_dot(input, pos)),
       b'(' => lex_a_token!(lex_lparen(input, pos)),
       b')' => lex_a_token!(lex_rparen(input, pos)),
       // b'{' => lex_a_token!(lex_lbrace(input, pos)),
       // b'}' => lex_a_token!(lex_rbrace(input, pos)),
       // b'[' => lex_a_token!(lex_lbracket(input, pos)),
       // b']' => lex_a_token!(lex_rbracket(input, pos)),
-      b' ' | b'\n' | b'\t' => {
+      b' ' | b'\n' | b'\t' | b'\r' => {
         let ((), p) = skip_spaces(input, pos)?;
         pos = p;
       }
+      b';' => {
+        let ((), p) = skip_comment(input, pos)?;
+        pos = p;
+      }
+      b if is_symbol_byte(b) => lex_a_token!(lex_symbol(input, pos)),
       b => return Err(LexError::invalid_char(b as char, Loc(pos, pos + 1))),
     }
   }
   Ok(tokens)
-}
\ No newline at end of file
+}
+
+/// Lexes `input` whose first byte sits at offset `base` of a source map.
+pub fn lex_at(input: &str, base: usize) -> Result<Vec<Token>, LexError> {
+  match lex(input) {
+    Ok(tokens) => Ok(tokens.into_iter()
+      .map(|tok| Token::new(tok.value, tok.loc.shift(base)))
+      .collect()),
+    Err(e) => Err(LexError::new(e.value, e.loc.shift(base))),
+  }
+}
+
+/// Whether `input` lexes but leaves a paren, a quote or a string unclosed,
+/// i.e. more input is needed before it can be parsed.
+pub fn is_incomplete(input: &str) -> bool {
+  use super::token::TokenKind::*;
+  let tokens = match lex(input) {
+    Ok(tokens) => tokens,
+    Err(e) => return e.value == LexErrorKind::UnterminatedString,
+  };
+  let mut depth = 0usize;
+  for tok in &tokens {
+    match tok.value {
+      LParen => depth += 1,
+      RParen if depth == 0 => return false,
+      RParen => depth -= 1,
+      _ => {},
+    }
+  }
+  depth > 0 || tokens.last().is_some_and(|tok| tok.value == Quote)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::token::TokenKind;
+
+  fn kinds(input: &str) -> Vec<TokenKind> {
+    lex(input).unwrap().into_iter().map(|tok| tok.value).collect()
+  }
+
+  /// The single number `input` lexes to, as it is printed.
+  fn number(input: &str) -> Result<String, LexErrorKind> {
+    match lex(input).map_err(|e| e.value)?.as_slice() {
+      [Token { value: TokenKind::Number(n), .. }] => Ok(n.to_string()),
+      tokens => panic!("{} lexed to {:?}", input, tokens),
+    }
+  }
+
+  #[test]
+  fn integer_literals() {
+    assert_eq!(number("-5"), Ok(String::from("-5")));
+    assert_eq!(number("+5"), Ok(String::from("5")));
+    assert_eq!(number("1_000"), Ok(String::from("1000")));
+    assert_eq!(number("99999999999"), Ok(String::from("99999999999")));
+    assert_eq!(number("-2147483648"), Ok(String::from("-2147483648")));
+    assert_eq!(number("#x1F"), Ok(String::from("31")));
+    assert_eq!(number("#b-101"), Ok(String::from("-5")));
+    assert_eq!(number("#o17"), Ok(String::from("15")));
+    assert_eq!(number("#d10"), Ok(String::from("10")));
+    for input in &["1__0", "1_", "#x", "#xG", "#b2", "-5_"] {
+      assert_eq!(number(input), Err(LexErrorKind::InvalidNumber), "{}", input);
+    }
+    assert_eq!(lex("#z1").map_err(|e| e.value), Err(LexErrorKind::InvalidChar('#')));
+  }
+
+  #[test]
+  fn float_literals() {
+    assert_eq!(number("1.5"), Ok(String::from("1.5")));
+    assert_eq!(number(".5"), Ok(String::from("0.5")));
+    assert_eq!(number("-.5"), Ok(String::from("-0.5")));
+    assert_eq!(number("1."), Ok(String::from("1.0")));
+    assert_eq!(number("1e-3"), Ok(String::from("0.001")));
+    assert_eq!(number("2E+2"), Ok(String::from("200.0")));
+    assert_eq!(number("+inf.0"), Ok(String::from("+inf.0")));
+    assert_eq!(number("-inf.0"), Ok(String::from("-inf.0")));
+    assert_eq!(number("+nan.0"), Ok(String::from("+nan.0")));
+    for input in &["1e", "1.5.2", "1e+", "inf.0"] {
+      assert_eq!(kinds(input), vec![TokenKind::Symbol(Box::from(*input))], "{}", input);
+    }
+    assert_eq!(kinds("(a . b)")[2], TokenKind::Dot);
+  }
+
+  #[test]
+  fn rational_literals() {
+    assert_eq!(number("1/3"), Ok(String::from("1/3")));
+    assert_eq!(number("-2/4"), Ok(String::from("-1/2")));
+    assert_eq!(number("6/3"), Ok(String::from("2")));
+    assert_eq!(number("#x-1/A"), Ok(String::from("-1/10")));
+    for input in &["1/0", "1/", "1//2", "-1/0"] {
+      assert_eq!(number(input), Err(LexErrorKind::InvalidNumber), "{}", input);
+    }
+    for input in &["1/x", "1/-2"] {
+      assert_eq!(kinds(input), vec![TokenKind::Symbol(Box::from(*input))], "{}", input);
+    }
+  }
+
+  #[test]
+  fn boolean_literals() {
+    assert_eq!(kinds("#t #false (#true)"), vec![
+      TokenKind::Boolean(true),
+      TokenKind::Boolean(false),
+      TokenKind::LParen,
+      TokenKind::Boolean(true),
+      TokenKind::RParen,
+    ]);
+    for input in &["#t#f", "#tru", "#f1", "#true#"] {
+      let e = lex(input).unwrap_err();
+      assert_eq!((e.value, e.loc), (LexErrorKind::InvalidChar('#'), Loc(0, 1)), "{}", input);
+    }
+  }
+
+  #[test]
+  fn signs_and_digits_may_start_identifiers() {
+    for input in &["1+", "-", "+", "-x", "...", "1st"] {
+      assert_eq!(kinds(input), vec![TokenKind::Symbol(Box::from(*input))], "{}", input);
+    }
+    assert_eq!(kinds("(-5 - 5)"), kinds("( -5 - 5 )"));
+    assert_eq!(kinds("(- 5)").len(), 4);
+  }
+
+  #[test]
+  fn crlf_and_comments_are_skipped() {
+    let tokens = kinds("; a script\r\n(f x) ; trailing\r\n;; last line");
+    assert_eq!(tokens, kinds("(f x)"));
+    assert_eq!(kinds("\"a ; b\""), vec![TokenKind::String(Box::from("a ; b"))]);
+  }
+}
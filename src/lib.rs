@@ -4,17 +4,25 @@ pub mod ast;
 pub mod parser;
 pub mod error;
 pub mod disp;
+pub mod bigint;
+pub mod number;
 pub mod data;
+pub mod env;
+pub mod editor;
 pub mod interpreter;
+pub mod source;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Loc(usize, usize);
 
 impl Loc {
-  fn merge(&self, other: &Loc) -> Loc {
+  pub fn merge(&self, other: &Loc) -> Loc {
     use std::cmp::{max, min};
     Loc(min(self.0, other.0), max(self.1, other.1))
   }
+  fn shift(&self, base: usize) -> Loc {
+    Loc(self.0 + base, self.1 + base)
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -27,4 +35,10 @@ impl<T> Annot<T> {
   fn new(value: T, loc: Loc) -> Self {
     Self { value, loc }
   }
+  pub fn value(&self) -> &T {
+    &self.value
+  }
+  pub fn loc(&self) -> Loc {
+    self.loc
+  }
 }
@@ -1,51 +1,282 @@
 extern crate rlisp;
-use rlisp::ast::Ast;
-use rlisp::error::show_trace;
-use rlisp::interpreter::Interpreter;
+use rlisp::error::{parse_file, read_file, Error};
+use rlisp::editor::Editor;
+use rlisp::interpreter::{Interpreter, SPECIAL_FORMS, STACK_SIZE};
+use rlisp::lexer::{is_incomplete, lex};
+use rlisp::source::SourceMap;
 use std::io;
+use std::process;
+use std::time::Instant;
 
-fn prompt(s: &str) -> io::Result<()> {
-  use io::{stdout, Write};
-  let stdout = stdout();
-  let mut stdout = stdout.lock();
-  stdout.write(s.as_bytes())?;
-  stdout.flush()
+const USAGE: &str = "usage: rlisp [--error-format=human|json] [file [args...]] | [-e expr [args...]]";
+
+/// The name `-e` expressions are reported under.
+const EXPR_NAME: &str = "<expr>";
+
+const COMMANDS: &[&str] = &[":tokens", ":ast", ":env", ":time", ":load", ":reset", ":help", ":quit"];
+
+const HELP: &str = "\
+:tokens expr   show the tokens of expr
+:ast [expr]    show the AST of expr, or toggle showing it before each evaluation
+:env           list global bindings
+:time expr     evaluate expr and report the elapsed time
+:load file     evaluate a source file
+:reset         discard all definitions
+:help          show this message
+:quit          leave the REPL";
+
+/// How errors are written to stderr.
+#[derive(Clone, Copy)]
+enum ErrorFormat {
+  Human,
+  /// One JSON object per line, for editors and CI.
+  Json,
 }
 
-fn main() -> io::Result<()> {
-  use io::{stdin, BufRead, BufReader};
-  let mut interp = Interpreter::new();
+struct Repl {
+  interp: Interpreter,
+  sources: SourceMap,
+  command_line: Vec<String>,
+  show_ast: bool,
+  error_format: ErrorFormat,
+}
 
-  let stdin = stdin();
-  let stdin = stdin.lock();
-  let stdin = BufReader::new(stdin);
-  let mut lines = stdin.lines();
+impl Repl {
+  fn new(command_line: Vec<String>, error_format: ErrorFormat) -> Self {
+    let mut interp = Interpreter::new();
+    interp.set_command_line(&command_line);
+    Repl { interp, sources: SourceMap::new(), command_line, show_ast: false, error_format }
+  }
 
-  loop {
-    prompt("> ")?;
-    if let Some(Ok(line)) = lines.next() {
-      if line == "exit" { break; }
-      let ast = match line.parse::<Ast>() {
-        Ok(ast) => ast,
+  fn eval(&mut self, input: &str) {
+    let name = format!("<repl #{}>", self.sources.len() + 1);
+    let program = match parse_file(&mut self.sources, &name, input) {
+      Ok(program) => program,
+      Err(errors) => {
+        report(&errors, &self.sources, self.error_format);
+        return;
+      }
+    };
+    for ast in program {
+      if self.show_ast {
+        println!("{:?}", ast);
+      }
+      match self.interp.eval(&ast) {
+        Ok(n) if n.is_unspecified() => {},
+        Ok(n) => println!("{:#}", n),
         Err(e) => {
-          e.show_diagnostic(&line);
-          show_trace(e);
-          continue;
+          report(&[Error::from(e)], &self.sources, self.error_format);
+          break;
         }
-      };
-      println!("{:?}", ast);
-      let n = match interp.eval(&ast) {
-        Ok(n) => n,
+      }
+    }
+  }
+
+  /// Runs a meta-command line such as `:load file`. Returns false on `:quit`.
+  fn command(&mut self, line: &str) -> bool {
+    let (command, arg) = match line.find(char::is_whitespace) {
+      Some(i) => (&line[..i], line[i..].trim()),
+      None => (line, ""),
+    };
+    match command {
+      ":tokens" => match lex(arg) {
+        Ok(tokens) => for tok in tokens {
+          println!("{}\t{:?}", tok.loc(), tok.value());
+        },
         Err(e) => {
-          e.show_diagnostic(&line);
-          show_trace(e);
-          continue;
+          let mut sources = SourceMap::new();
+          sources.add("<tokens>", arg);
+          report(&[Error::from(e)], &sources, self.error_format);
+        },
+      },
+      ":ast" if arg.is_empty() => {
+        self.show_ast = !self.show_ast;
+        println!("AST dumping {}", if self.show_ast { "on" } else { "off" });
+      },
+      ":ast" => {
+        let mut sources = SourceMap::new();
+        match parse_file(&mut sources, "<ast>", arg) {
+          Ok(program) => for ast in program {
+            println!("{:?}", ast);
+          },
+          Err(errors) => report(&errors, &sources, self.error_format),
         }
+      },
+      ":env" => for (name, value) in self.interp.globals() {
+        println!("{}\t{:#}", name, value);
+      },
+      ":time" => {
+        let start = Instant::now();
+        self.eval(arg);
+        println!(";; {:?}", start.elapsed());
+      },
+      ":load" => match read_file(arg) {
+        Ok(source) => {
+          run(&mut self.interp, &mut self.sources, arg, &source, false, self.error_format);
+        },
+        Err(e) => report(&[e], &self.sources, self.error_format),
+      },
+      ":reset" => *self = Repl::new(self.command_line.clone(), self.error_format),
+      ":help" => println!("{}", HELP),
+      ":quit" | ":q" => return false,
+      _ => eprintln!("unknown command {}, try :help", command),
+    }
+    true
+  }
+
+  /// Words offered by tab completion.
+  fn completions(&self) -> Vec<String> {
+    self.interp.globals().into_iter()
+      .map(|(name, _)| String::from(name))
+      .chain(SPECIAL_FORMS.iter().chain(COMMANDS).map(|word| word.to_string()))
+      .collect()
+  }
+
+  fn run(&mut self) -> io::Result<()> {
+    let mut editor = Editor::new(Editor::default_history_path());
+    let mut input = String::new();
+
+    loop {
+      editor.set_completions(self.completions());
+      let line = match editor.read_line(if input.is_empty() { "> " } else { ".. " }) {
+        Ok(Some(line)) => line,
+        Ok(None) => break,
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+          input.clear();
+          continue;
+        },
+        Err(e) => return Err(e),
       };
-      println!("{}", n);
-    } else {
-      break;
+      editor.add_history(&line);
+      if input.is_empty() {
+        if line.trim().is_empty() { continue; }
+        if line.starts_with(':') {
+          if !self.command(line.trim()) { break; }
+          continue;
+        }
+      } else {
+        input.push('\n');
+      }
+      input.push_str(&line);
+      // keep reading until every paren is closed
+      if is_incomplete(&input) {
+        continue;
+      }
+      self.eval(&input);
+      input.clear();
+    };
+    Ok(())
+  }
+}
+
+fn report(errors: &[Error], sources: &SourceMap, format: ErrorFormat) {
+  for e in errors {
+    match format {
+      ErrorFormat::Human => e.show_diagnostic(sources),
+      ErrorFormat::Json => eprintln!("{}", e.diagnostic(sources).to_json(sources)),
     }
+  }
+}
+
+/// Evaluates a whole source text, printing the value of the last expression
+/// if `print` is set. Returns whether evaluation succeeded.
+fn run(interp: &mut Interpreter, sources: &mut SourceMap, name: &str, source: &str, print: bool, format: ErrorFormat) -> bool {
+  let result = parse_file(sources, name, source)
+    .and_then(|program| interp.eval_program(&program).map_err(|e| vec![Error::from(e)]));
+  match result {
+    Ok(value) => {
+      if print && !value.is_unspecified() {
+        println!("{:#}", value);
+      }
+      true
+    },
+    Err(errors) => {
+      report(&errors, sources, format);
+      false
+    }
+  }
+}
+
+fn main() {
+  // deep recursion must reach the interpreter's own limit before the stack runs out
+  let interpreter = std::thread::Builder::new()
+    .stack_size(STACK_SIZE)
+    .spawn(start)
+    .expect("failed to start the interpreter thread");
+  // `start` exits the process itself unless it panicked
+  if interpreter.join().is_err() {
+    process::exit(101);
+  }
+}
+
+/// What `command-line` returns when running a script or `-e` expression:
+/// its name followed by its arguments.
+fn script_args(args: &[String]) -> Vec<String> {
+  match args.get(1).map(String::as_str) {
+    Some("-e") => std::iter::once(EXPR_NAME.to_string()).chain(args.iter().skip(3).cloned()).collect(),
+    _ => args.iter().skip(1).cloned().collect(),
+  }
+}
+
+fn start() {
+  use io::Write;
+  let mut args = std::env::args().collect::<Vec<_>>();
+  let mut format = ErrorFormat::Human;
+  loop {
+    format = match args.get(1).and_then(|arg| arg.strip_prefix("--error-format=")) {
+      Some("human") => ErrorFormat::Human,
+      Some("json") => ErrorFormat::Json,
+      Some(_) => {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+      },
+      None => break,
+    };
+    args.remove(1);
+  }
+  let mut interp = Interpreter::new();
+  let mut sources = SourceMap::new();
+
+  let ok = match args.get(1).map(String::as_str) {
+    None => Repl::new(args[..1].to_vec(), format).run().is_ok(),
+    Some("-e") => match args.get(2) {
+      Some(expr) => {
+        interp.set_command_line(&script_args(&args));
+        run(&mut interp, &mut sources, EXPR_NAME, expr, true, format)
+      },
+      None => {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+      },
+    },
+    Some("-h") | Some("--help") => {
+      println!("{}", USAGE);
+      true
+    },
+    Some(path) => match read_file(path) {
+      Ok(source) => {
+        interp.set_command_line(&script_args(&args));
+        run(&mut interp, &mut sources, path, &source, false, format)
+      },
+      Err(e) => {
+        report(&[e], &sources, format);
+        false
+      },
+    },
   };
-  Ok(())
+  let _ = io::stdout().flush();
+  process::exit(if ok { 0 } else { 1 });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn command_line_starts_with_the_script_name() {
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    assert_eq!(script_args(&args(&["rlisp", "file.lisp", "a", "b"])), args(&["file.lisp", "a", "b"]));
+    assert_eq!(script_args(&args(&["rlisp", "-e", "(command-line)", "a", "b"])), args(&["<expr>", "a", "b"]));
+    assert_eq!(script_args(&args(&["rlisp", "-e", "(command-line)"])), args(&["<expr>"]));
+  }
 }
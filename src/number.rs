@@ -0,0 +1,470 @@
+use super::bigint::BigInt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// A number of the numeric tower: an exact integer, an exact rational or an
+/// inexact real. Arithmetic on mixed operands yields an inexact result if
+/// either is inexact. Exact integers are kept in an `i32` whenever they fit
+/// and promoted to a `BigInt` otherwise, so exact arithmetic never overflows;
+/// a rational whose denominator reduces to 1 becomes an integer.
+#[derive(Debug, Clone)]
+pub enum Number {
+  Int(i32),
+  Big(BigInt),
+  Ratio(Box<Ratio>),
+  Float(f64),
+}
+
+/// An exact fraction in lowest terms with a denominator greater than 1.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ratio {
+  numer: BigInt,
+  denom: BigInt,
+}
+
+use self::Number::*;
+
+impl Number {
+  /// Demotes to `Int` when the value fits.
+  pub fn from_big(n: BigInt) -> Number {
+    match n.to_i32() {
+      Some(n) => Int(n),
+      None => Big(n),
+    }
+  }
+  /// The exact fraction `numer/denom`. `denom` must not be zero.
+  pub fn ratio(numer: BigInt, denom: BigInt) -> Number {
+    let gcd = numer.gcd(&denom);
+    Number::reduced(numer.div_rem(&gcd).0, denom.div_rem(&gcd).0)
+  }
+  /// The fraction `numer/denom` of coprime integers, skipping the gcd.
+  fn reduced(numer: BigInt, denom: BigInt) -> Number {
+    let (numer, denom) = if denom.is_negative() { (numer.neg(), denom.neg()) } else { (numer, denom) };
+    match denom.to_i32() {
+      Some(1) => Number::from_big(numer),
+      _ => Ratio(Box::new(Ratio { numer, denom })),
+    }
+  }
+  pub fn is_exact(&self) -> bool {
+    match self {
+      Int(_) | Big(_) | Ratio(_) => true,
+      Float(_) => false,
+    }
+  }
+  pub fn is_integer(&self) -> bool {
+    match *self {
+      Int(_) | Big(_) => true,
+      Ratio(_) => false,
+      Float(x) => x.is_finite() && x.fract() == 0.0,
+    }
+  }
+  pub fn is_zero(&self) -> bool {
+    match *self {
+      Int(n) => n == 0,
+      Big(_) | Ratio(_) => false,
+      Float(x) => x == 0.0,
+    }
+  }
+  /// Whether this is an exact zero, the only divisor that is an error.
+  pub fn is_exact_zero(&self) -> bool {
+    matches!(*self, Int(0))
+  }
+  pub fn is_negative(&self) -> bool {
+    match *self {
+      Int(n) => n < 0,
+      Big(ref n) => n.is_negative(),
+      Ratio(ref r) => r.numer.is_negative(),
+      Float(x) => x < 0.0,
+    }
+  }
+  pub fn to_f64(&self) -> f64 {
+    match *self {
+      Int(n) => f64::from(n),
+      Big(ref n) => n.to_f64(),
+      Ratio(ref r) => r.numer.to_f64() / r.denom.to_f64(),
+      Float(x) => x,
+    }
+  }
+  fn to_big(&self) -> Option<BigInt> {
+    match *self {
+      Int(n) => Some(BigInt::from_i64(i64::from(n))),
+      Big(ref n) => Some(n.clone()),
+      Ratio(_) | Float(_) => None,
+    }
+  }
+  /// An exact number as a fraction, integers having a denominator of 1.
+  fn to_ratio(&self) -> Option<Ratio> {
+    match *self {
+      Ratio(ref r) => Some((**r).clone()),
+      _ => self.to_big().map(|numer| Ratio { numer, denom: BigInt::from_i64(1) }),
+    }
+  }
+  pub fn to_inexact(&self) -> Number {
+    Float(self.to_f64())
+  }
+  /// The exact number equal to this one; `None` for infinities and NaN.
+  pub fn to_exact(&self) -> Option<Number> {
+    match *self {
+      Float(x) if x.is_finite() => {
+        // every finite float is an integer divided by a power of two
+        let (mut m, mut shift) = (x, 0);
+        while m.fract() != 0.0 {
+          m *= 2.0;
+          shift += 1;
+        }
+        Some(Number::ratio(BigInt::from_f64(m), BigInt::from_i64(2).pow(shift)))
+      },
+      Float(_) => None,
+      _ => Some(self.clone()),
+    }
+  }
+  /// The literal for this number in `radix`; `None` for an inexact number
+  /// in a radix other than 10.
+  pub fn to_string_radix(&self, radix: u32) -> Option<String> {
+    match *self {
+      _ if radix == 10 => Some(self.to_string()),
+      Int(n) => Some(BigInt::from_i64(i64::from(n)).to_radix(radix)),
+      Big(ref n) => Some(n.to_radix(radix)),
+      Ratio(ref r) => Some(format!("{}/{}", r.numer.to_radix(radix), r.denom.to_radix(radix))),
+      Float(_) => None,
+    }
+  }
+  /// The numerator in lowest terms, inexact if this number is; `None` for
+  /// infinities and NaN.
+  pub fn numerator(&self) -> Option<Number> {
+    let n = match self.to_exact()? {
+      Ratio(r) => Number::from_big(r.numer),
+      n => n,
+    };
+    Some(if self.is_exact() { n } else { n.to_inexact() })
+  }
+  /// The positive denominator in lowest terms, inexact if this number is.
+  pub fn denominator(&self) -> Option<Number> {
+    let n = match self.to_exact()? {
+      Ratio(r) => Number::from_big(r.denom),
+      _ => Int(1),
+    };
+    Some(if self.is_exact() { n } else { n.to_inexact() })
+  }
+  /// Numeric comparison; `None` if either side is NaN.
+  pub fn compare(&self, other: &Number) -> Option<Ordering> {
+    match (self, other) {
+      (Int(a), Int(b)) => Some(a.cmp(b)),
+      (a, b) if a.is_exact() && b.is_exact() => {
+        let (a, b) = (a.to_ratio()?, b.to_ratio()?);
+        Some(a.numer.mul(&b.denom).cmp(&b.numer.mul(&a.denom)))
+      },
+      (a, b) => a.to_f64().partial_cmp(&b.to_f64()),
+    }
+  }
+  /// Applies `small` to two `Int`s, falling back to `big` when it overflows
+  /// or both operands are integers, to `ratio` when both are exact and to
+  /// `float` when either is inexact.
+  fn binary(
+    &self,
+    other: &Number,
+    small: fn(i32, i32) -> Option<i32>,
+    big: fn(&BigInt, &BigInt) -> BigInt,
+    ratio: fn(&Ratio, &Ratio) -> Number,
+    float: fn(f64, f64) -> f64,
+  ) -> Number {
+    if let (Int(a), Int(b)) = (self, other) {
+      if let Some(n) = small(*a, *b) {
+        return Int(n);
+      }
+    }
+    if let (Some(a), Some(b)) = (self.to_big(), other.to_big()) {
+      return Number::from_big(big(&a, &b));
+    }
+    match (self.to_ratio(), other.to_ratio()) {
+      (Some(a), Some(b)) => ratio(&a, &b),
+      _ => Float(float(self.to_f64(), other.to_f64())),
+    }
+  }
+  pub fn add(&self, other: &Number) -> Number {
+    self.binary(other, i32::checked_add, BigInt::add, |a, b| {
+      Number::ratio(a.numer.mul(&b.denom).add(&b.numer.mul(&a.denom)), a.denom.mul(&b.denom))
+    }, |a, b| a + b)
+  }
+  pub fn sub(&self, other: &Number) -> Number {
+    self.add(&other.neg())
+  }
+  pub fn mul(&self, other: &Number) -> Number {
+    self.binary(other, i32::checked_mul, BigInt::mul, |a, b| {
+      Number::ratio(a.numer.mul(&b.numer), a.denom.mul(&b.denom))
+    }, |a, b| a * b)
+  }
+  /// Exact division yields a rational; the caller rules out an exact zero divisor.
+  pub fn div(&self, other: &Number) -> Number {
+    match (self.to_ratio(), other.to_ratio()) {
+      (Some(a), Some(b)) => Number::ratio(a.numer.mul(&b.denom), a.denom.mul(&b.numer)),
+      _ => Float(self.to_f64() / other.to_f64()),
+    }
+  }
+  /// Applies `big` to integers, or `float` if either is inexact.
+  fn integer_binary(&self, other: &Number, big: fn(&BigInt, &BigInt) -> BigInt, float: fn(f64, f64) -> f64) -> Number {
+    match (self.to_big(), other.to_big()) {
+      (Some(a), Some(b)) => Number::from_big(big(&a, &b)),
+      _ => Float(float(self.to_f64(), other.to_f64())),
+    }
+  }
+  /// The truncated quotient of integers; the divisor must not be zero.
+  pub fn quotient(&self, other: &Number) -> Number {
+    self.integer_binary(other, |a, b| a.div_rem(b).0, |a, b| (a / b).trunc())
+  }
+  /// The remainder of integers, with the sign of the dividend.
+  pub fn remainder(&self, other: &Number) -> Number {
+    self.integer_binary(other, |a, b| a.div_rem(b).1, |a, b| a % b)
+  }
+  /// The remainder of integers, with the sign of the divisor.
+  pub fn modulo(&self, other: &Number) -> Number {
+    let r = self.remainder(other);
+    if !r.is_zero() && r.is_negative() != other.is_negative() {
+      r.add(other)
+    } else {
+      r
+    }
+  }
+  /// The greatest common divisor of exact integers, never negative;
+  /// `None` for inexact operands.
+  pub fn gcd(&self, other: &Number) -> Option<Number> {
+    Some(Number::from_big(self.to_big()?.gcd(&other.to_big()?)))
+  }
+  /// Bitwise and of exact integers; `None` for any other operand.
+  pub fn and(&self, other: &Number) -> Option<Number> {
+    self.bitwise(other, |a, b| a & b, BigInt::and)
+  }
+  /// Bitwise inclusive or of exact integers; `None` for any other operand.
+  pub fn or(&self, other: &Number) -> Option<Number> {
+    self.bitwise(other, |a, b| a | b, BigInt::or)
+  }
+  fn bitwise(&self, other: &Number, small: fn(i32, i32) -> i32, big: fn(&BigInt, &BigInt) -> BigInt) -> Option<Number> {
+    match (self, other) {
+      (Int(a), Int(b)) => Some(Int(small(*a, *b))),
+      _ => Some(Number::from_big(big(&self.to_big()?, &other.to_big()?))),
+    }
+  }
+  pub fn neg(&self) -> Number {
+    match *self {
+      Int(n) => n.checked_neg().map_or_else(|| Number::from_big(BigInt::from_i64(-i64::from(n))), Int),
+      Big(ref n) => Number::from_big(n.neg()),
+      Ratio(ref r) => Ratio(Box::new(Ratio { numer: r.numer.neg(), denom: r.denom.clone() })),
+      Float(x) => Float(-x),
+    }
+  }
+  pub fn abs(&self) -> Number {
+    if self.is_negative() { self.neg() } else { self.clone() }
+  }
+  fn round_with(&self, ratio: fn(&Ratio) -> BigInt, float: fn(f64) -> f64) -> Number {
+    match *self {
+      Ratio(ref r) => Number::from_big(ratio(r)),
+      Float(x) => Float(float(x)),
+      _ => self.clone(),
+    }
+  }
+  pub fn floor(&self) -> Number {
+    self.round_with(Ratio::floor, f64::floor)
+  }
+  pub fn ceiling(&self) -> Number {
+    // a `Ratio` is never an integer
+    self.round_with(|r| r.floor().add(&BigInt::from_i64(1)), f64::ceil)
+  }
+  pub fn truncate(&self) -> Number {
+    self.round_with(|r| r.numer.div_rem(&r.denom).0, f64::trunc)
+  }
+  /// Rounds to the nearest integer, ties to even.
+  pub fn round(&self) -> Number {
+    self.round_with(|r| {
+      let floor = r.floor();
+      let twice_fraction = r.numer.sub(&floor.mul(&r.denom)).mul(&BigInt::from_i64(2));
+      let even = floor.div_rem(&BigInt::from_i64(2)).1.is_zero();
+      match twice_fraction.cmp(&r.denom) {
+        Ordering::Less => floor,
+        Ordering::Equal if even => floor,
+        _ => floor.add(&BigInt::from_i64(1)),
+      }
+    }, f64::round_ties_even)
+  }
+  /// The integer square root `s` and remainder `n - s * s` of a non-negative
+  /// exact integer.
+  pub fn exact_integer_sqrt(&self) -> Option<(Number, Number)> {
+    let n = self.to_big().filter(|n| !n.is_negative())?;
+    let s = n.isqrt();
+    let r = n.sub(&s.mul(&s));
+    Some((Number::from_big(s), Number::from_big(r)))
+  }
+  /// Exact for exact squares such as `4` or `1/9`, inexact otherwise.
+  pub fn sqrt(&self) -> Number {
+    let exact_root = |n: BigInt| match Number::from_big(n).exact_integer_sqrt() {
+      Some((s, r)) if r.is_zero() => s.to_big(),
+      _ => None,
+    };
+    let root = self.to_ratio().and_then(|r| Some(Number::ratio(exact_root(r.numer)?, exact_root(r.denom)?)));
+    root.unwrap_or_else(|| Float(self.to_f64().sqrt()))
+  }
+  /// Exact for an exact base and an exact integer exponent. `None` if the
+  /// exact result would be too large to compute. The caller rules out an
+  /// exact zero base with a negative exponent.
+  pub fn pow(&self, exponent: &Number) -> Option<Number> {
+    // about 300,000 decimal digits
+    const MAX_BITS: u64 = 1 << 20;
+    let base = match self.to_ratio() {
+      Some(base) if exponent.is_exact() && exponent.is_integer() => base,
+      _ => return Some(Float(self.to_f64().powf(exponent.to_f64()))),
+    };
+    match (self, exponent) {
+      // these stay small whatever the exponent
+      (Int(0), _) | (Int(1), _) if !exponent.is_zero() => Some(self.clone()),
+      (Int(-1), _) => Some(Int(if exponent.remainder(&Int(2)).is_zero() { 1 } else { -1 })),
+      // the result has about `e` times as many bits as the base
+      (_, &Int(e)) if u64::from(e.unsigned_abs()) * base.numer.bits().max(base.denom.bits()) as u64 <= MAX_BITS => {
+        let (numer, denom) = (base.numer.pow(e.unsigned_abs()), base.denom.pow(e.unsigned_abs()));
+        // powers of coprime integers are coprime
+        Some(if e < 0 { Number::reduced(denom, numer) } else { Number::reduced(numer, denom) })
+      },
+      _ => None,
+    }
+  }
+  /// The simplest rational within `tolerance` of this number: the one with
+  /// the smallest denominator. Inexact if either argument is.
+  pub fn rationalize(&self, tolerance: &Number) -> Option<Number> {
+    let x = self.to_exact()?;
+    let tolerance = tolerance.to_exact()?.abs();
+    let simplest = simplest_between(&x.sub(&tolerance), &x.add(&tolerance));
+    Some(if self.is_exact() && tolerance.is_exact() { simplest } else { simplest.to_inexact() })
+  }
+}
+
+impl Ratio {
+  pub fn numer(&self) -> &BigInt {
+    &self.numer
+  }
+  pub fn denom(&self) -> &BigInt {
+    &self.denom
+  }
+  fn floor(&self) -> BigInt {
+    let (q, r) = self.numer.div_rem(&self.denom);
+    if r.is_negative() { q.sub(&BigInt::from_i64(1)) } else { q }
+  }
+}
+
+/// The rational with the smallest denominator in the closed interval
+/// `[lo, hi]` of exact numbers, found by walking their continued fractions.
+fn simplest_between(lo: &Number, hi: &Number) -> Number {
+  if (lo.is_negative() || lo.is_zero()) && !hi.is_negative() {
+    return Int(0);
+  }
+  if hi.is_negative() {
+    return simplest_between(&hi.neg(), &lo.neg()).neg();
+  }
+  // the smallest integer in the interval, if there is one
+  let floor = lo.floor();
+  if floor.compare(lo) == Some(Ordering::Equal) {
+    return floor;
+  }
+  let next = floor.add(&Int(1));
+  if next.compare(hi) != Some(Ordering::Greater) {
+    return next;
+  }
+  let reciprocal = |n: Number| Int(1).div(&n);
+  let rest = simplest_between(&reciprocal(hi.sub(&floor)), &reciprocal(lo.sub(&floor)));
+  floor.add(&reciprocal(rest))
+}
+
+/// Structural equality, as `eqv?`: an exact and an inexact number are never
+/// equal and floats are compared by representation, so `+nan.0` equals itself.
+/// Use `compare` for numeric equality.
+impl PartialEq for Number {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Int(a), Int(b)) => a == b,
+      (Big(a), Big(b)) => a == b,
+      (Ratio(a), Ratio(b)) => a == b,
+      (Float(a), Float(b)) => a.to_bits() == b.to_bits(),
+      _ => false,
+    }
+  }
+}
+
+impl Eq for Number {}
+
+impl Hash for Number {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    match *self {
+      Int(n) => (0, n).hash(state),
+      Big(ref n) => (1, n).hash(state),
+      Ratio(ref r) => (2, r).hash(state),
+      Float(x) => (3, x.to_bits()).hash(state),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn q(numer: i64, denom: i64) -> Number {
+    Number::ratio(BigInt::from_i64(numer), BigInt::from_i64(denom))
+  }
+
+  #[test]
+  fn ratios_are_normalized() {
+    assert_eq!(q(2, -4).to_string(), "-1/2");
+    assert_eq!(q(-6, -4).to_string(), "3/2");
+    assert_eq!(q(-6, -3), Int(2));
+    assert_eq!(q(0, -5), Int(0));
+    assert_eq!(q(-(1 << 32), 2), Int(i32::MIN));
+    assert_eq!(q(1 << 32, 2), Big(BigInt::from_i64(1 << 31)));
+    assert_eq!(q(1, 3).mul(&Int(3)), Int(1));
+    assert_eq!(q(1, 6).add(&q(1, 3)), q(1, 2));
+  }
+
+  #[test]
+  fn round_ties_to_even() {
+    let rounded = |n: Number| n.round().to_string();
+    assert_eq!(rounded(q(1, 2)), "0");
+    assert_eq!(rounded(q(3, 2)), "2");
+    assert_eq!(rounded(q(5, 2)), "2");
+    assert_eq!(rounded(q(-5, 2)), "-2");
+    assert_eq!(rounded(q(-3, 2)), "-2");
+    assert_eq!(rounded(q(7, 3)), "2");
+    assert_eq!(rounded(q(-8, 3)), "-3");
+    assert_eq!(rounded(Float(2.5)), "2.0");
+    assert_eq!(rounded(Float(3.5)), "4.0");
+    assert_eq!(rounded(Float(-2.5)), "-2.0");
+  }
+
+  #[test]
+  fn to_exact() {
+    assert_eq!(Float(0.5).to_exact(), Some(q(1, 2)));
+    assert_eq!(Float(-0.1).to_exact(), Some(q(-3602879701896397, 36028797018963968)));
+    assert_eq!(Float(1e20).to_exact().map(|n| n.to_string()), Some(String::from("100000000000000000000")));
+    assert_eq!(Float(-0.0).to_exact(), Some(Int(0)));
+    let tiny = Number::ratio(BigInt::from_i64(1), BigInt::from_i64(2).pow(1074));
+    assert_eq!(Float(5e-324).to_exact(), Some(tiny));
+    assert_eq!(Float(f64::INFINITY).to_exact(), None);
+    assert_eq!(Float(f64::NAN).to_exact(), None);
+    assert_eq!(q(1, 3).to_exact(), Some(q(1, 3)));
+  }
+
+  #[test]
+  fn rationalize() {
+    assert_eq!(q(3, 10).rationalize(&q(1, 10)), Some(q(1, 3)));
+    assert_eq!(q(-3, 10).rationalize(&q(1, 10)), Some(q(-1, 3)));
+    assert_eq!(q(1, 4).rationalize(&q(1, 4)), Some(Int(0)));
+    assert_eq!(q(5, 2).rationalize(&q(-1, 2)), Some(Int(2)));
+    assert_eq!(q(22, 7).rationalize(&Int(0)), Some(q(22, 7)));
+    assert_eq!(Float(0.3).rationalize(&q(1, 10)), Some(Float(1.0 / 3.0)));
+    assert_eq!(Float(f64::NAN).rationalize(&Int(1)), None);
+  }
+
+  #[test]
+  fn pow_is_bounded_by_the_result_size() {
+    assert_eq!(q(2, 3).pow(&Int(-3)), Some(q(27, 8)));
+    assert_eq!(q(-2, 3).pow(&Int(3)), Some(q(-8, 27)));
+    assert_eq!(Int(-1).pow(&Big(BigInt::from_i64(1 << 40))), Some(Int(1)));
+    assert_eq!(Int(3).pow(&Int(16_000_000)), None);
+    assert_eq!(Int(2).pow(&Big(BigInt::from_i64(1 << 40))), None);
+    assert_eq!(Int(2).pow(&Int(1000)).map(|n| n.to_string().len()), Some(302));
+  }
+}
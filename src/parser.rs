@@ -1,134 +1,322 @@
 use super::Loc;
 use super::token::{TokenKind, Token};
-use super::ast::{Op, Ast};
+use super::ast::Ast;
 use std::iter::Peekable;
 
+/// How deeply lists and quotes may nest. Parsing, quoting and printing a
+/// value each recurse once per level.
+pub const MAX_NESTING: usize = 1000;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParseError {
   UnexpectedToken(Token),
   NotExpression(Token),
-  NotOperator(Token),
+  /// The opening paren of a list still open at the end of input.
   UnclosedOpenParen(Token),
-  RedundantExpression(Token),
+  /// The list or quote nested more than `MAX_NESTING` deep.
+  TooDeep(Token),
   Eof,
 }
 
-// atom
-fn parse_atom<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
+/// Parser state: errors are collected rather than returned, and the parser
+/// carries on with an error node in place of whatever could not be parsed.
+struct Parser<Tokens>
 where
   Tokens: Iterator<Item = Token>,
 {
-  tokens
-    .next()
-    .ok_or(ParseError::Eof)
-    .and_then(|tok| match tok.value {
-      // NUMBER
-	  TokenKind::Number(n) => Ok(Ast::num(n, tok.loc)),
-	  TokenKind::Plus     => Ok(Ast::op(Op::add(tok.loc), tok.loc)),
-	  TokenKind::Minus    => Ok(Ast::op(Op::sub(tok.loc), tok.loc)),
-	  TokenKind::Asterisk => Ok(Ast::op(Op::mul(tok.loc), tok.loc)),
-	  TokenKind::Slash    => Ok(Ast::op(Op::div(tok.loc), tok.loc)),
-	  TokenKind::Less     => Ok(Ast::op(Op::lt(tok.loc), tok.loc)),
-	  TokenKind::Equal    => Ok(Ast::op(Op::equal(tok.loc), tok.loc)),
-	  TokenKind::Greater  => Ok(Ast::op(Op::gt(tok.loc), tok.loc)),
-	  TokenKind::And      => Ok(Ast::op(Op::and(tok.loc), tok.loc)),
-	  TokenKind::Or       => Ok(Ast::op(Op::or(tok.loc), tok.loc)),
-      _ => Err(ParseError::NotExpression(tok)),
-    })
+  tokens: Peekable<Tokens>,
+  errors: Vec<ParseError>,
+  /// Number of lists currently open.
+  depth: usize,
+  /// Number of lists and quotes currently open.
+  nesting: usize,
+  /// Location just past the last token consumed.
+  end: Loc,
 }
 
-/// cdr  : ')'
-///      | '.' list ')'
-///      | list cdr
-///      ;
-fn parse_cdr<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
+impl<Tokens> Parser<Tokens>
 where
   Tokens: Iterator<Item = Token>,
 {
-  let tok = tokens.peek().ok_or(ParseError::Eof)?.clone();
-  match tok.value {
-      TokenKind::RParen => {
-        tokens.next();
-        Ok(Ast::nil(tok.loc))
-      },
-      TokenKind::Dot => {
-        tokens.next();
-        let t = parse_list(tokens)?;
-        let tok = tokens.peek().ok_or(ParseError::Eof)?.clone();
-        match tok.value {
-            TokenKind::RParen => {
-              tokens.next();
-              Ok(t)
-            },
-            _ => Err(ParseError::UnclosedOpenParen(tok)),
-        }
-      },
+  fn new(tokens: Tokens) -> Self {
+    Parser { tokens: tokens.peekable(), errors: Vec::new(), depth: 0, nesting: 0, end: Loc(0, 0) }
+  }
+
+  fn peek(&mut self) -> Option<Token> {
+    self.tokens.peek().cloned()
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    let tok = self.tokens.next()?;
+    self.end = Loc(tok.loc.1, tok.loc.1);
+    Some(tok)
+  }
+
+  // atom
+  fn parse_atom(&mut self) -> Ast {
+    let tok = match self.next() {
+      Some(tok) => tok,
+      None => return self.parse_eof(),
+    };
+    match tok.value {
+      // NUMBER
+      TokenKind::Number(n) => Ast::num(n, tok.loc),
+      // BOOLEAN
+      TokenKind::Boolean(b) => Ast::boolean(b, tok.loc),
+      // SYMBOL
+      TokenKind::Symbol(ref name) => Ast::symbol(name, tok.loc),
+      // STRING
+      TokenKind::String(ref s) => Ast::string(s, tok.loc),
+      // CHAR
+      TokenKind::Char(c) => Ast::character(c, tok.loc),
       _ => {
-        let l = parse_list(tokens)?;
-        let r = parse_cdr(tokens)?;
-        let loc = l.loc;
-        Ok(Ast::pair(l, r, loc))
+        let loc = tok.loc;
+        self.errors.push(ParseError::NotExpression(tok));
+        Ast::error(loc)
       },
     }
-}
+  }
 
-/// pair : ')'
-///      | list cdr
-///      ;
-fn parse_pair<Tokens>(tokens: &mut Peekable<Tokens>, loc: Loc) -> Result<Ast, ParseError>
-where
-  Tokens: Iterator<Item = Token>,
-{
-  match tokens.peek()
-    .ok_or(ParseError::Eof)?
-    .value {
-      TokenKind::RParen => {
-        tokens.next();
-        Ok(Ast::nil(loc))
-      },
-      _ => {
-        let l = parse_list(tokens)?;
-        let r = parse_cdr(tokens)?;
-        Ok(Ast::pair(l, r, loc))
-      },
+  /// An error node for a missing expression. Inside a list the unclosed
+  /// paren is reported instead.
+  fn parse_eof(&mut self) -> Ast {
+    if self.depth == 0 {
+      self.errors.push(ParseError::Eof);
     }
-}
+    Ast::error(self.end)
+  }
 
-/// list : "(" pair
-///      | "'"  list
-///      | ATOM
-///      ;
-fn parse_list<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
-where
-  Tokens: Iterator<Item = Token>,
-{
-  let tok = tokens.peek().ok_or(ParseError::Eof)?.clone();
-  match tok.value {
-      TokenKind::LParen => {
-        tokens.next();
-        parse_pair(tokens, tok.loc)
-      },
-      TokenKind::Quote => {
-        tokens.next();
-        let q = parse_list(tokens)?;
-        Ok(Ast::quote(q, tok.loc))
-      },
-      _ => parse_atom(tokens),
+  /// Skips to the paren closing the innermost open list and consumes it.
+  /// Returns its location, or `None` at the end of input.
+  fn synchronize(&mut self) -> Option<Loc> {
+    let mut depth = 0;
+    loop {
+      let tok = self.next()?;
+      match tok.value {
+        TokenKind::LParen => depth += 1,
+        TokenKind::RParen if depth == 0 => return Some(tok.loc),
+        TokenKind::RParen => depth -= 1,
+        _ => {},
+      }
+    }
+  }
+
+  /// Skips the expression starting at the next token.
+  fn skip_expr(&mut self) {
+    while let Some(tok) = self.next() {
+      match tok.value {
+        TokenKind::Quote => {},
+        TokenKind::LParen => {
+          self.synchronize();
+          return;
+        },
+        _ => return,
+      }
     }
+  }
+
+  /// cdr  : ')'
+  ///      | '.' list ')'
+  ///      | list cdr
+  ///      ;
+  /// Also returns the location of the closing paren. The elements are
+  /// collected in a loop so that long lists do not use up the stack.
+  fn parse_cdr(&mut self, open: &Token) -> (Ast, Loc) {
+    let mut items = Vec::new();
+    let (tail, end) = loop {
+      let tok = match self.peek() {
+        Some(tok) => tok,
+        None => break self.unclosed(open),
+      };
+      match tok.value {
+          TokenKind::RParen => {
+            self.next();
+            break (Ast::nil(tok.loc), tok.loc);
+          },
+          TokenKind::Dot => {
+            self.next();
+            let t = self.parse_list();
+            let tok = match self.peek() {
+              Some(tok) => tok,
+              None => break (t, self.unclosed(open).1),
+            };
+            match tok.value {
+                TokenKind::RParen => {
+                  self.next();
+                  break (t, tok.loc);
+                },
+                _ => {
+                  self.errors.push(ParseError::UnexpectedToken(tok));
+                  match self.synchronize() {
+                    Some(end) => break (t, end),
+                    None => break (t, self.unclosed(open).1),
+                  }
+                },
+            }
+          },
+          _ => items.push(self.parse_list()),
+        }
+    };
+    let list = items.into_iter().rev().fold(tail, |r, l| {
+      let loc = l.loc;
+      Ast::pair(l, r, loc)
+    });
+    (list, end)
+  }
+
+  /// Reports `open` as never closed and ends its list at the end of input.
+  fn unclosed(&mut self, open: &Token) -> (Ast, Loc) {
+    self.errors.push(ParseError::UnclosedOpenParen(open.clone()));
+    (Ast::nil(self.end), self.end)
+  }
+
+  /// pair : ')'
+  ///      | list cdr
+  ///      ;
+  fn parse_pair(&mut self, open: Token) -> Ast {
+    let tok = match self.peek() {
+      Some(tok) => tok,
+      None => return Ast::error(open.loc.merge(&self.unclosed(&open).1)),
+    };
+    self.depth += 1;
+    let ast = match tok.value {
+        TokenKind::RParen => {
+          self.next();
+          Ast::nil(open.loc.merge(&tok.loc))
+        },
+        _ => {
+          let l = self.parse_list();
+          let (r, end) = self.parse_cdr(&open);
+          Ast::pair(l, r, open.loc.merge(&end))
+        },
+      };
+    self.depth -= 1;
+    ast
+  }
+
+  /// list : "(" pair
+  ///      | "'"  list
+  ///      | ATOM
+  ///      ;
+  fn parse_list(&mut self) -> Ast {
+    let tok = match self.peek() {
+      Some(tok) => tok,
+      None => return self.parse_eof(),
+    };
+    match tok.value {
+        TokenKind::LParen | TokenKind::Quote if self.nesting == MAX_NESTING => {
+          self.errors.push(ParseError::TooDeep(tok.clone()));
+          self.skip_expr();
+          Ast::error(tok.loc.merge(&self.end))
+        },
+        TokenKind::LParen => {
+          self.next();
+          self.nesting += 1;
+          let ast = self.parse_pair(tok);
+          self.nesting -= 1;
+          ast
+        },
+        TokenKind::Quote => {
+          self.next();
+          self.nesting += 1;
+          let q = self.parse_list();
+          self.nesting -= 1;
+          Ast::quote(q, tok.loc)
+        },
+        // leave a closing paren to the list it closes
+        TokenKind::RParen => {
+          self.errors.push(ParseError::NotExpression(tok.clone()));
+          Ast::error(tok.loc)
+        },
+        _ => self.parse_atom(),
+      }
+  }
+
+  fn parse_expr(&mut self) -> Ast {
+    self.parse_list()
+  }
+
+  /// input : list input
+  ///       | /* empty */
+  ///       ;
+  /// After a stray token, resumes at the next '(' so one mistake yields one error.
+  fn parse_program(&mut self) -> Vec<Ast> {
+    let mut program = Vec::new();
+    while let Some(tok) = self.peek() {
+      match tok.value {
+        TokenKind::RParen | TokenKind::Dot => {
+          self.errors.push(ParseError::UnexpectedToken(tok.clone()));
+          program.push(Ast::error(tok.loc));
+          self.next();
+          while self.peek().is_some_and(|tok| tok.value != TokenKind::LParen) {
+            self.next();
+          }
+        },
+        _ => program.push(self.parse_expr()),
+      }
+    }
+    program
+  }
 }
 
-fn parse_expr<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
-where
-  Tokens: Iterator<Item = Token>,
-{
-  parse_list(tokens)
+/// Parses every top-level expression, returning only the first error.
+pub fn parse_program(tokens: Vec<Token>) -> Result<Vec<Ast>, ParseError> {
+  let (program, errors) = parse_program_recovering(tokens);
+  match errors.into_iter().next() {
+    Some(e) => Err(e),
+    None => Ok(program),
+  }
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Ast, ParseError> {
-  let mut tokens = tokens.into_iter().peekable();
-  let ret = parse_expr(&mut tokens)?;
-  match tokens.next() {
-    Some(tok) => Err(ParseError::RedundantExpression(tok)),
-    None => Ok(ret),
+/// Parses every top-level expression, recovering from errors. The program
+/// contains an error node wherever something could not be parsed.
+pub fn parse_program_recovering(tokens: Vec<Token>) -> (Vec<Ast>, Vec<ParseError>) {
+  let mut parser = Parser::new(tokens.into_iter());
+  let program = parser.parse_program();
+  (program, parser.errors)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::ast::AstKind;
+  use super::super::lexer::lex;
+
+  fn errors(input: &str) -> Vec<ParseError> {
+    parse_program_recovering(lex(input).unwrap()).1
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn recovers_to_report_every_error() {
+    let errors = errors("(+ 1 . 2 3) ) (car '(1 2)) (. 1) (f x");
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+    assert!(matches!(errors[0], ParseError::UnexpectedToken(Token { value: TokenKind::Number(_), .. })));
+    assert!(matches!(errors[1], ParseError::UnexpectedToken(Token { value: TokenKind::RParen, .. })));
+    assert!(matches!(errors[2], ParseError::NotExpression(Token { value: TokenKind::Dot, .. })));
+    assert!(matches!(errors[3], ParseError::UnclosedOpenParen(Token { value: TokenKind::LParen, .. })));
+  }
+
+  #[test]
+  fn long_lists_parse() {
+    let input = format!("(list {})", "1 ".repeat(100_000));
+    let (program, errors) = parse_program_recovering(lex(&input).unwrap());
+    assert!(errors.is_empty());
+    let mut len = 0;
+    let mut list = &program[0];
+    while let AstKind::Pair { ref r, .. } = list.value {
+      len += 1;
+      list = r;
+    }
+    assert_eq!(len, 100_001);
+  }
+
+  #[test]
+  fn nesting_is_limited() {
+    let nested = |depth| format!("'{}{}", "(".repeat(depth - 1), ")".repeat(depth - 1));
+    assert!(errors(&nested(MAX_NESTING)).is_empty());
+    let errors = errors(&format!("{} (f x", nested(1_000_000)));
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(matches!(errors[0], ParseError::TooDeep(Token { value: TokenKind::LParen, .. })));
+    assert!(matches!(errors[1], ParseError::UnclosedOpenParen(_)));
+  }
+}
@@ -0,0 +1,98 @@
+use super::Loc;
+
+/// A named source text registered in a `SourceMap`.
+pub struct SourceFile {
+  name: String,
+  text: String,
+  start: usize,
+  line_starts: Vec<usize>,
+}
+
+/// A resolved location: 1-based line and column (in characters).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+  pub line: usize,
+  pub column: usize,
+}
+
+/// Every source text seen so far, laid out one after another so that a
+/// `Loc` is unique across files and REPL inputs.
+#[derive(Default)]
+pub struct SourceMap {
+  files: Vec<SourceFile>,
+}
+
+impl SourceFile {
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+  /// Offset of the first byte of this file in the source map.
+  pub fn start(&self) -> usize {
+    self.start
+  }
+  pub fn end(&self) -> usize {
+    self.start + self.text.len()
+  }
+  pub fn line_count(&self) -> usize {
+    self.line_starts.len()
+  }
+  /// The text of a 1-based line, without its terminator.
+  pub fn line(&self, line: usize) -> &str {
+    let start = self.line_starts[line - 1];
+    let end = self.line_starts.get(line).map_or(self.text.len(), |&next| next - 1);
+    self.text[start..end].trim_end_matches('\r')
+  }
+  /// Resolves an offset of the source map, clamped to this file.
+  pub fn position(&self, offset: usize) -> Position {
+    let offset = offset.saturating_sub(self.start).min(self.text.len());
+    let line = self.line_starts.partition_point(|&start| start <= offset);
+    let line_start = self.line_starts[line - 1];
+    let column = self.text.get(line_start..offset).map_or(offset - line_start, |s| s.chars().count()) + 1;
+    Position { line, column }
+  }
+}
+
+impl SourceMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Registers a source text and returns the offset its `Loc`s start at.
+  pub fn add(&mut self, name: &str, text: &str) -> usize {
+    // leave a gap so that an end-of-file location still maps to this file
+    let start = self.files.last().map_or(0, |file| file.end() + 1);
+    let line_starts = std::iter::once(0)
+      .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    self.files.push(SourceFile {
+      name: String::from(name),
+      text: String::from(text),
+      start,
+      line_starts,
+    });
+    start
+  }
+  pub fn len(&self) -> usize {
+    self.files.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.files.is_empty()
+  }
+  /// The most recently added file.
+  pub fn last(&self) -> Option<&SourceFile> {
+    self.files.last()
+  }
+  /// The file containing `offset`.
+  pub fn file(&self, offset: usize) -> Option<&SourceFile> {
+    let i = self.files.partition_point(|file| file.start <= offset);
+    self.files.get(i.checked_sub(1)?)
+      .filter(|file| offset <= file.end())
+  }
+  /// The file containing `loc` and its start position.
+  pub fn lookup(&self, loc: Loc) -> Option<(&SourceFile, Position)> {
+    let file = self.file(loc.0)?;
+    Some((file, file.position(loc.0)))
+  }
+}
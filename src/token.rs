@@ -1,17 +1,13 @@
 use super::{Loc, Annot};
+use super::number::Number;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TokenKind {
-  Number(u32),
-  Plus,
-  Minus,
-  Asterisk,
-  Slash,
-  Less,
-  Equal,
-  Greater,
-  And,
-  Or,
+  Number(Number),
+  Boolean(bool),
+  Symbol(Box<str>),
+  String(Box<str>),
+  Char(char),
   Quote,
   Dot,
   LParen,
@@ -25,35 +21,20 @@ pub enum TokenKind {
 pub type Token = Annot<TokenKind>;
 
 impl Token {
-  pub fn number(n: u32, loc: Loc) -> Self {
+  pub fn number(n: Number, loc: Loc) -> Self {
     Self::new(TokenKind::Number(n), loc)
   }
-  pub fn plus(loc: Loc) -> Self {
-    Self::new(TokenKind::Plus, loc)
+  pub fn boolean(b: bool, loc: Loc) -> Self {
+    Self::new(TokenKind::Boolean(b), loc)
   }
-  pub fn minus(loc: Loc) -> Self {
-    Self::new(TokenKind::Minus, loc)
+  pub fn symbol(name: &str, loc: Loc) -> Self {
+    Self::new(TokenKind::Symbol(Box::from(name)), loc)
   }
-  pub fn asterisk(loc: Loc) -> Self {
-    Self::new(TokenKind::Asterisk, loc)
+  pub fn string(s: &str, loc: Loc) -> Self {
+    Self::new(TokenKind::String(Box::from(s)), loc)
   }
-  pub fn slash(loc: Loc) -> Self {
-    Self::new(TokenKind::Slash, loc)
-  }
-  pub fn less(loc: Loc) -> Self {
-    Self::new(TokenKind::Less, loc)
-  }
-  pub fn equal(loc: Loc) -> Self {
-    Self::new(TokenKind::Equal, loc)
-  }
-  pub fn greater(loc: Loc) -> Self {
-    Self::new(TokenKind::Greater, loc)
-  }
-  pub fn and(loc: Loc) -> Self {
-    Self::new(TokenKind::And, loc)
-  }
-  pub fn or(loc: Loc) -> Self {
-    Self::new(TokenKind::Or, loc)
+  pub fn character(c: char, loc: Loc) -> Self {
+    Self::new(TokenKind::Char(c), loc)
   }
   pub fn quote(loc: Loc) -> Self {
     Self::new(TokenKind::Quote, loc)